@@ -0,0 +1,604 @@
+//! `mvw`, a command-line client for MediathekViewWeb.
+//!
+//! ```text
+//! mvw search '!ard #tagesschau >10' --sort-by timestamp --format csv
+//! mvw search '#livestream' --format m3u > livestreams.m3u
+//! mvw download '#sendung,mit,der,maus' --size 3 --quality hd --output videos
+//! ```
+
+use std::{
+    collections::HashSet,
+    error::Error,
+    io::Write,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use futures_util::StreamExt;
+use mediathekviewweb::{
+    datetime::{BerlinTime, FromTimestamp},
+    download::{Downloader, Progress},
+    hls::VariantSelection,
+    m3u::M3u,
+    models::{Fallback, Item, Quality, SortField, SortOrder},
+    Mediathek, MediathekQueryBuilder,
+};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "mvw",
+    version,
+    about = "Search and download videos using MediathekViewWeb"
+)]
+struct Cli {
+    /// The URL of the MediathekViewWeb server.
+    #[arg(long, global = true, default_value = "https://mediathekviewweb.de")]
+    base_url: String,
+    /// The `User-Agent` header sent with each request.
+    #[arg(long, global = true, default_value = concat!("mvw/", env!("CARGO_PKG_VERSION")))]
+    user_agent: String,
+    /// The timeout of each request, e.g. `30s`.
+    #[arg(long, global = true, value_parser = parse_duration)]
+    timeout: Option<Duration>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Search for videos.
+    Search {
+        #[command(flatten)]
+        query: QueryArgs,
+        /// The output format.
+        #[arg(short, long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+    /// Download the videos that match a search.
+    Download {
+        #[command(flatten)]
+        query: QueryArgs,
+        /// The preferred quality. If a video isn't available in this quality,
+        /// the next lower quality is downloaded.
+        #[arg(short, long, value_enum, default_value_t = QualityArg::Hd)]
+        quality: QualityArg,
+        /// The directory in which the videos are saved.
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Debug, Args)]
+struct QueryArgs {
+    /// The search string, using the advanced search syntax, e.g.
+    /// `!ard #tagesschau >10`. Use `--` before a search string that starts
+    /// with `-`.
+    #[arg(required = true)]
+    query: Vec<String>,
+    /// Search terms without a prefix also match the description.
+    #[arg(short, long)]
+    everywhere: bool,
+    /// The minimum duration, e.g. `90s`, `10m` or `1h`. Plain numbers are
+    /// minutes.
+    #[arg(long, value_parser = parse_duration)]
+    duration_min: Option<Duration>,
+    /// The maximum duration, e.g. `90s`, `10m` or `1h`. Plain numbers are
+    /// minutes.
+    #[arg(long, value_parser = parse_duration)]
+    duration_max: Option<Duration>,
+    /// Include videos with a broadcasting date in the future.
+    #[arg(long, overrides_with = "no_future")]
+    future: bool,
+    /// Exclude videos with a broadcasting date in the future.
+    #[arg(long)]
+    no_future: bool,
+    /// Sort the results by a specific field.
+    #[arg(long, value_enum)]
+    sort_by: Option<SortBy>,
+    /// The sort order.
+    #[arg(long, value_enum)]
+    sort_order: Option<Order>,
+    /// The count of results per request.
+    #[arg(long)]
+    size: Option<usize>,
+    /// Skip the specified count of results.
+    #[arg(long)]
+    offset: Option<usize>,
+    /// Retrieve multiple pages until this count of results is reached.
+    #[arg(long)]
+    limit: Option<usize>,
+}
+impl QueryArgs {
+    fn build<'a>(
+        &self,
+        mediathek: &'a Mediathek,
+    ) -> mediathekviewweb::Result<MediathekQueryBuilder<'a>> {
+        let mut builder = mediathek.query_string(&self.query.join(" "), self.everywhere)?;
+        if let Some(duration_min) = self.duration_min {
+            builder = builder.duration_min(duration_min);
+        }
+        if let Some(duration_max) = self.duration_max {
+            builder = builder.duration_max(duration_max);
+        }
+        if self.future || self.no_future {
+            builder = builder.include_future(self.future);
+        }
+        if let Some(sort_by) = self.sort_by {
+            builder = builder.sort_by(match sort_by {
+                SortBy::Channel => SortField::Channel,
+                SortBy::Timestamp => SortField::Timestamp,
+                SortBy::Duration => SortField::Duration,
+            });
+        }
+        if let Some(sort_order) = self.sort_order {
+            builder = builder.sort_order(match sort_order {
+                Order::Asc => SortOrder::Ascending,
+                Order::Desc => SortOrder::Descending,
+            });
+        }
+        if let Some(size) = self.size {
+            builder = builder.size(size);
+        }
+        if let Some(offset) = self.offset {
+            builder = builder.offset(offset);
+        }
+        Ok(builder)
+    }
+
+    /// Send the query and return the results, retrieving multiple pages if a
+    /// limit is set.
+    async fn send(&self, mediathek: &Mediathek) -> mediathekviewweb::Result<Vec<Item>> {
+        let builder = self.build(mediathek)?;
+        match self.limit {
+            Some(limit) => builder
+                .limit(limit)
+                .items()
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect(),
+            None => Ok(builder.send().await?.results),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Csv,
+    M3u,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum SortBy {
+    Channel,
+    Timestamp,
+    Duration,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Order {
+    Asc,
+    Desc,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum QualityArg {
+    Low,
+    Normal,
+    Hd,
+}
+impl From<QualityArg> for Quality {
+    fn from(quality: QualityArg) -> Self {
+        match quality {
+            QualityArg::Low => Quality::Low,
+            QualityArg::Normal => Quality::Normal,
+            QualityArg::Hd => Quality::Hd,
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", error_message(&*e));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Return the message of an error including its sources.
+fn error_message(e: &dyn Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(e) = source {
+        message += &format!(": {e}");
+        source = e.source();
+    }
+    message
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let user_agent: reqwest::header::HeaderValue = cli.user_agent.parse()?;
+    let mut builder = Mediathek::builder()
+        .base_url(cli.base_url)
+        .user_agent(user_agent.clone());
+    if let Some(timeout) = cli.timeout {
+        builder = builder.timeout(timeout);
+    }
+    let mediathek = builder.build()?;
+
+    match cli.command {
+        Command::Search { query, format } => {
+            let items = query.send(&mediathek).await?;
+            let mut stdout = std::io::stdout().lock();
+            match format {
+                Format::Table => write_table(&mut stdout, &items)?,
+                Format::Json => {
+                    serde_json::to_writer_pretty(&mut stdout, &items)?;
+                    writeln!(stdout)?;
+                }
+                Format::Csv => write_csv(&mut stdout, &items)?,
+                Format::M3u => M3u::new().write(&mut stdout, &items)?,
+            }
+        }
+        Command::Download {
+            query,
+            quality,
+            output,
+        } => {
+            let items = query.send(&mediathek).await?;
+            std::fs::create_dir_all(&output)?;
+            let downloader = Downloader::new(user_agent)?;
+            let mut names = HashSet::new();
+            let mut failed = 0;
+            for item in &items {
+                let name = unique_name(&mut names, file_name(item));
+                // report failed downloads and continue with the next video
+                if let Err(e) = download(&downloader, item, &name, quality.into(), &output).await {
+                    eprintln!("error: {name}: {}", error_message(&e));
+                    failed += 1;
+                }
+            }
+            if failed > 0 {
+                return Err(format!("{failed} of {} downloads failed", items.len()).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Download the video of an item into a directory, printing the progress to
+/// stderr.
+async fn download(
+    downloader: &Downloader,
+    item: &Item,
+    name: &str,
+    quality: Quality,
+    output: &Path,
+) -> mediathekviewweb::Result<()> {
+    let Some((quality, url)) = item.select_url(quality, Fallback::Lower) else {
+        return Err(mediathekviewweb::Error::QualityUnavailable(quality));
+    };
+    let mut last_line = String::new();
+    let progress = |progress: Progress| {
+        let line = match progress.total {
+            Some(total) if total > 0 => {
+                format!("{name}: {}%", progress.downloaded * 100 / total)
+            }
+            _ => format!("{name}: {} MiB", progress.downloaded / (1024 * 1024)),
+        };
+        if line != last_line {
+            eprint!("\r{line}");
+            last_line = line;
+        }
+    };
+
+    let result = if item.is_hls() {
+        let path = output.join(format!("{name}.ts"));
+        downloader
+            .download_hls(url, VariantSelection::Highest, path, progress)
+            .await
+    } else {
+        let path = output.join(format!("{name}.{}", extension(url)));
+        downloader.download(item, quality, path, progress).await
+    };
+    if !last_line.is_empty() {
+        eprintln!();
+    }
+    result
+}
+
+/// Return a file name (without extension) for the video of an item.
+///
+/// The name contains the channel and the broadcasting date, so episodes with
+/// the same title, e.g. of daily news, don't overwrite each other.
+fn file_name(item: &Item) -> String {
+    let date = BerlinTime::from_timestamp(item.timestamp)
+        .map(|date| format!(" - {:04}-{:02}-{:02}", date.year, date.month, date.day))
+        .unwrap_or_default();
+    let name = format!("{} - {} - {}{date}", item.channel, item.topic, item.title)
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(200)
+        .collect::<String>();
+    name.trim().trim_start_matches('.').to_owned()
+}
+
+/// Append a number to a file name if it has already been used.
+fn unique_name(names: &mut HashSet<String>, name: String) -> String {
+    let mut unique = name.clone();
+    let mut i = 2;
+    while !names.insert(unique.clone()) {
+        unique = format!("{name} ({i})");
+        i += 1;
+    }
+    unique
+}
+
+/// Return the file extension of a video URL, defaulting to `mp4`.
+fn extension(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let file = path.rsplit('/').next().unwrap_or_default();
+    match file.rsplit_once('.') {
+        Some((_, extension))
+            if (1..=4).contains(&extension.len())
+                && extension.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            extension
+        }
+        _ => "mp4",
+    }
+}
+
+fn write_table(mut w: impl Write, items: &[Item]) -> std::io::Result<()> {
+    let rows = items
+        .iter()
+        .map(|item| {
+            [
+                format_timestamp(item.timestamp),
+                item.channel.to_string(),
+                truncate(&item.topic, 30),
+                truncate(&item.title, 60),
+                item.duration.map(format_duration).unwrap_or_default(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = ["Date", "Channel", "Topic", "Title", "Duration"].map(String::from);
+
+    let mut widths = [0; 5];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(w, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+fn write_csv(mut w: impl Write, items: &[Item]) -> std::io::Result<()> {
+    writeln!(
+        w,
+        "channel,topic,title,timestamp,duration,url_video,url_website,id"
+    )?;
+    for item in items {
+        let fields = [
+            item.channel.to_string(),
+            item.topic.clone(),
+            item.title.clone(),
+            item.timestamp.to_string(),
+            item.duration
+                .map(|duration| duration.as_secs().to_string())
+                .unwrap_or_default(),
+            item.url_video.clone(),
+            item.url_website.clone(),
+            item.id.clone(),
+        ];
+        let fields = fields
+            .iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<_>>();
+        writeln!(w, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// Quote a CSV field if necessary.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_owned()
+    } else {
+        text.chars().take(max_chars - 1).chain(['…']).collect()
+    }
+}
+
+/// Format a UNIX timestamp as `YYYY-MM-DD HH:MM` in the Europe/Berlin time
+/// zone.
+fn format_timestamp(timestamp: i64) -> String {
+    BerlinTime::from_timestamp(timestamp)
+        .map(|date| {
+            format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}",
+                date.year, date.month, date.day, date.hour, date.minute
+            )
+        })
+        .unwrap_or_default()
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Parse a duration like `90s`, `10m` or `1h`. Plain numbers are minutes.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, factor) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1),
+        Some((i, 'm')) => (&s[..i], 60),
+        Some((i, 'h')) => (&s[..i], 3600),
+        _ => (s, 60),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(factor))
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("invalid duration `{s}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, time::Duration};
+
+    use clap::Parser;
+    use mediathekviewweb::{
+        models::{Query, QueryField, SortField, SortOrder},
+        Mediathek,
+    };
+
+    use super::{
+        csv_field, extension, format_duration, format_timestamp, parse_duration, truncate,
+        unique_name, Cli, Command, Format, QualityArg,
+    };
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("15"), Ok(Duration::from_secs(900)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("1.5h").is_err());
+        assert_eq!(
+            parse_duration("18446744073709551615h"),
+            Err("invalid duration `18446744073709551615h`".into())
+        );
+        let error = Cli::try_parse_from([
+            "mvw",
+            "search",
+            "tagesschau",
+            "--duration-min",
+            "18446744073709551615h",
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn test_format() {
+        // timestamps are shown in the Europe/Berlin time zone
+        assert_eq!(format_timestamp(0), "1970-01-01 01:00");
+        assert_eq!(format_timestamp(1_709_236_800), "2024-02-29 21:00");
+        assert_eq!(format_timestamp(1_696_269_600), "2023-10-02 20:00");
+        assert_eq!(format_timestamp(i64::MAX), "");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05");
+        assert_eq!(truncate("Tagesschau", 5), "Tage…");
+        assert_eq!(csv_field("a, \"b\""), "\"a, \"\"b\"\"\"");
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(extension("https://example.org/video.webm?x=1"), "webm");
+        assert_eq!(extension("https://example.org/video"), "mp4");
+
+        let mut names = HashSet::new();
+        assert_eq!(unique_name(&mut names, "tagesschau".into()), "tagesschau");
+        assert_eq!(
+            unique_name(&mut names, "tagesschau".into()),
+            "tagesschau (2)"
+        );
+        assert_eq!(
+            unique_name(&mut names, "tagesschau".into()),
+            "tagesschau (3)"
+        );
+    }
+
+    /// The example in the documentation searches for the entire topic.
+    #[test]
+    fn test_example() {
+        let cli = Cli::try_parse_from([
+            "mvw",
+            "download",
+            "#sendung,mit,der,maus",
+            "--size",
+            "3",
+            "--quality",
+            "hd",
+            "--output",
+            "videos",
+        ])
+        .unwrap();
+        let Command::Download { query, quality, .. } = cli.command else {
+            panic!("not a download command")
+        };
+        assert!(matches!(quality, QualityArg::Hd));
+
+        let mediathek = Mediathek::builder().build().unwrap();
+        let query = query.build(&mediathek).unwrap().into_query();
+        assert_eq!(
+            query.queries,
+            [Query::new([QueryField::Topic], "sendung mit der maus")]
+        );
+        assert_eq!(query.size, Some(3));
+    }
+
+    #[test]
+    fn test_cli() {
+        let cli = Cli::try_parse_from([
+            "mvw",
+            "search",
+            "!ard",
+            "#tagesschau",
+            "--duration-min",
+            "10",
+            "--sort-by",
+            "timestamp",
+            "--sort-order",
+            "desc",
+            "--format",
+            "csv",
+        ])
+        .unwrap();
+        let Command::Search { query, format } = cli.command else {
+            panic!("not a search command")
+        };
+        assert!(matches!(format, Format::Csv));
+        assert_eq!(query.query.join(" "), "!ard #tagesschau");
+        assert_eq!(query.duration_min, Some(Duration::from_secs(600)));
+
+        let mediathek = Mediathek::builder().build().unwrap();
+        let query = query.build(&mediathek).unwrap().into_query();
+        assert_eq!(query.sort_by, Some(SortField::Timestamp));
+        assert_eq!(query.sort_order, Some(SortOrder::Descending));
+        assert_eq!(query.duration_min, Some(Duration::from_secs(600)));
+        assert_eq!(query.future, None);
+    }
+}
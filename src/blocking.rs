@@ -0,0 +1,482 @@
+//! A blocking client for the MediathekViewWeb API.
+//!
+//! This mirrors the asynchronous API of [`crate::Mediathek`] for applications
+//! that don't use an async runtime.
+//!
+//! # Example
+//! ```rust,no_run
+//! # fn main() -> mediathekviewweb::Result<()> {
+//! # #[allow(non_snake_case)]
+//! # let USER_AGENT = "example".try_into().unwrap();
+//! let results = mediathekviewweb::blocking::Mediathek::new(USER_AGENT)?
+//!     .query([mediathekviewweb::models::QueryField::Topic], "tagesschau")
+//!     .size(2)
+//!     .send()?;
+//!
+//! println!("{results:#?}");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    cache::{CacheStats, QueryCache},
+    models::{Channel, Item, MediathekQuery, Query, QueryField, QueryResult, SortField, SortOrder},
+    retry::{RateLimiter, RetryPolicy},
+    search_string::SearchString,
+    Pagination,
+};
+
+/// A blocking client for a MediathekViewWeb server.
+///
+/// Requests are sent by an asynchronous [`crate::Mediathek`] on a
+/// single-threaded Tokio runtime that is owned by the client. As with
+/// [`reqwest::blocking`], the client must not be used from within an async
+/// runtime.
+#[derive(Clone, Debug)]
+pub struct Mediathek {
+    inner: crate::Mediathek,
+    runtime: Arc<tokio::runtime::Runtime>,
+}
+impl Mediathek {
+    /// Create a new client for the official server hosted at <https://mediathekviewweb.de>.
+    ///
+    /// See [`crate::Mediathek::new`].
+    pub fn new(user_agent: reqwest::header::HeaderValue) -> crate::Result<Self> {
+        Self::new_with_url("https://mediathekviewweb.de", user_agent)
+    }
+
+    /// Create a new client for a MediathekViewWeb server hosted at a specific
+    /// URL.
+    ///
+    /// See [`crate::Mediathek::new_with_url`].
+    pub fn new_with_url(
+        base_url: impl Into<String>,
+        user_agent: reqwest::header::HeaderValue,
+    ) -> crate::Result<Self> {
+        Self::builder()
+            .base_url(base_url)
+            .user_agent(user_agent)
+            .build()
+    }
+
+    /// Create a [`MediathekBuilder`] to configure a new client.
+    pub fn builder() -> MediathekBuilder {
+        MediathekBuilder::default()
+    }
+
+    /// Retry failed requests according to a [`RetryPolicy`].
+    ///
+    /// See [`crate::Mediathek::with_retry_policy`].
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.inner = self.inner.with_retry_policy(retry_policy);
+        self
+    }
+
+    /// Limit the rate of requests sent to the server.
+    ///
+    /// See [`crate::Mediathek::with_rate_limit`].
+    pub fn with_rate_limit(mut self, rate_limiter: RateLimiter) -> Self {
+        self.inner = self.inner.with_rate_limit(rate_limiter);
+        self
+    }
+
+    /// Cache the results of queries using a [`QueryCache`].
+    ///
+    /// See [`crate::Mediathek::with_cache`].
+    pub fn with_cache(mut self, cache: QueryCache) -> Self {
+        self.inner = self.inner.with_cache(cache);
+        self
+    }
+
+    /// Return the hit and miss counts of the [cache](Self::with_cache), if
+    /// one is used.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+}
+impl Mediathek {
+    /// Query the current media database.
+    ///
+    /// See [`crate::Mediathek::query`].
+    pub fn query(
+        &self,
+        fields: impl Into<Vec<QueryField>>,
+        query: impl Into<String>,
+    ) -> MediathekQueryBuilder<'_> {
+        self.execute(MediathekQuery::default()).query(fields, query)
+    }
+    /// Query the current media database by parsing a query string.
+    ///
+    /// See [`crate::Mediathek::query_string`].
+    pub fn query_string(
+        &self,
+        query: &str,
+        search_everywhere: bool,
+    ) -> crate::Result<MediathekQueryBuilder<'_>> {
+        Ok(self.execute(MediathekQuery::from_search_string(
+            query,
+            search_everywhere,
+        )?))
+    }
+    /// Query the current media database using an already parsed
+    /// [`SearchString`].
+    ///
+    /// See [`crate::Mediathek::query_search_string`].
+    pub fn query_search_string(
+        &self,
+        query: &SearchString,
+        search_everywhere: bool,
+    ) -> MediathekQueryBuilder<'_> {
+        self.execute(MediathekQuery::from_parsed_search_string(
+            query,
+            search_everywhere,
+        ))
+    }
+    /// Query the current media database using a prepared [`MediathekQuery`].
+    pub fn execute(&self, query: MediathekQuery) -> MediathekQueryBuilder<'_> {
+        MediathekQueryBuilder {
+            client: self,
+            query,
+            limit: None,
+        }
+    }
+
+    fn send_query(&self, query: &MediathekQuery) -> crate::Result<QueryResult> {
+        self.runtime.block_on(self.inner.send_query(query))
+    }
+}
+
+/// A builder to configure a blocking [`Mediathek`] client.
+///
+/// Created using [`Mediathek::builder`]. See [`crate::MediathekBuilder`].
+///
+/// # Example
+/// ```rust
+/// # fn main() -> mediathekviewweb::Result<()> {
+/// use std::time::Duration;
+///
+/// use mediathekviewweb::{blocking::Mediathek, retry::RetryPolicy};
+///
+/// let mediathek = Mediathek::builder()
+///     .user_agent("example".try_into().unwrap())
+///     .timeout(Duration::from_secs(30))
+///     .retry_policy(RetryPolicy::new())
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct MediathekBuilder {
+    inner: crate::MediathekBuilder,
+}
+impl MediathekBuilder {
+    /// Use the MediathekViewWeb server hosted at a specific URL.
+    ///
+    /// See [`crate::MediathekBuilder::base_url`].
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.inner = self.inner.base_url(base_url);
+        self
+    }
+    /// Set the `User-Agent` header.
+    ///
+    /// See [`crate::MediathekBuilder::user_agent`].
+    pub fn user_agent(mut self, user_agent: reqwest::header::HeaderValue) -> Self {
+        self.inner = self.inner.user_agent(user_agent);
+        self
+    }
+    /// Add a header that is sent with every request.
+    pub fn header(
+        mut self,
+        name: reqwest::header::HeaderName,
+        value: reqwest::header::HeaderValue,
+    ) -> Self {
+        self.inner = self.inner.header(name, value);
+        self
+    }
+    /// Add headers that are sent with every request.
+    pub fn default_headers(mut self, headers: reqwest::header::HeaderMap) -> Self {
+        self.inner = self.inner.default_headers(headers);
+        self
+    }
+    /// Set a timeout for each request.
+    ///
+    /// See [`crate::MediathekBuilder::timeout`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.timeout(timeout);
+        self
+    }
+    /// Set a timeout for connecting to the server.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.inner = self.inner.connect_timeout(connect_timeout);
+        self
+    }
+    /// Send requests through a proxy.
+    ///
+    /// See [`crate::MediathekBuilder::proxy`].
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.inner = self.inner.proxy(proxy);
+        self
+    }
+    /// Retry failed requests according to a [`RetryPolicy`].
+    ///
+    /// See [`crate::Mediathek::with_retry_policy`].
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.inner = self.inner.retry_policy(retry_policy);
+        self
+    }
+    /// Limit the rate of requests sent to the server.
+    ///
+    /// See [`crate::Mediathek::with_rate_limit`].
+    pub fn rate_limit(mut self, rate_limiter: RateLimiter) -> Self {
+        self.inner = self.inner.rate_limit(rate_limiter);
+        self
+    }
+    /// Cache the results of queries.
+    ///
+    /// See [`crate::Mediathek::with_cache`].
+    pub fn cache(mut self, cache: QueryCache) -> Self {
+        self.inner = self.inner.cache(cache);
+        self
+    }
+
+    /// Build the client.
+    ///
+    /// See [`crate::MediathekBuilder::build`].
+    pub fn build(self) -> crate::Result<Mediathek> {
+        let inner = self.inner.build()?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Mediathek {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+}
+
+/// Blocking request builder for the `/api/query` endpoint.
+///
+/// See [`crate::MediathekQueryBuilder`].
+#[derive(Debug)]
+pub struct MediathekQueryBuilder<'client> {
+    client: &'client Mediathek,
+    query: MediathekQuery,
+    limit: Option<usize>,
+}
+impl MediathekQueryBuilder<'_> {
+    /// Add an additional search query.
+    ///
+    /// See [`crate::MediathekQueryBuilder::query`].
+    pub fn query(mut self, fields: impl Into<Vec<QueryField>>, query: impl Into<String>) -> Self {
+        self.query.queries.push(Query {
+            fields: fields.into(),
+            query: query.into(),
+        });
+        self
+    }
+    /// Exclude results that match a search query.
+    ///
+    /// See [`crate::MediathekQueryBuilder::exclude`].
+    pub fn exclude(mut self, fields: impl Into<Vec<QueryField>>, query: impl Into<String>) -> Self {
+        self.query.exclude.push(Query::new(fields, query));
+        self
+    }
+    /// Add a group of search queries of which at least one must match.
+    ///
+    /// See [`crate::MediathekQueryBuilder::any`].
+    pub fn any(mut self, queries: impl IntoIterator<Item = Query>) -> Self {
+        self.query.any.push(queries.into_iter().collect());
+        self
+    }
+    /// Only include results of a channel.
+    ///
+    /// See [`crate::MediathekQueryBuilder::channel`].
+    pub fn channel(mut self, channel: Channel) -> Self {
+        self.query.channels.push(channel);
+        self
+    }
+    /// Filter for a minimum duration.
+    pub fn duration_min(mut self, duration_min: impl Into<Duration>) -> Self {
+        self.query.duration_min = Some(duration_min.into());
+        self
+    }
+    /// Filter for a maximum duration.
+    pub fn duration_max(mut self, duration_max: impl Into<Duration>) -> Self {
+        self.query.duration_max = Some(duration_max.into());
+        self
+    }
+    /// Include media with a broadcasting date in the future.
+    pub fn include_future(mut self, include_future: bool) -> Self {
+        self.query.future = Some(include_future);
+        self
+    }
+    /// Only include media that are broadcast at or after `time`.
+    ///
+    /// See [`crate::MediathekQueryBuilder::broadcast_after`].
+    pub fn broadcast_after(mut self, time: impl Into<SystemTime>) -> Self {
+        self.query.broadcast_after = Some(crate::date::unix_timestamp(time.into()));
+        self.query.sort_by.get_or_insert(SortField::Timestamp);
+        self
+    }
+    /// Only include media that are broadcast before `time`.
+    ///
+    /// See [`crate::MediathekQueryBuilder::broadcast_before`].
+    pub fn broadcast_before(mut self, time: impl Into<SystemTime>) -> Self {
+        self.query.broadcast_before = Some(crate::date::unix_timestamp(time.into()));
+        self.query.sort_by.get_or_insert(SortField::Timestamp);
+        self
+    }
+    /// Sort the results by a specific field.
+    pub fn sort_by(mut self, sort_by: SortField) -> Self {
+        self.query.sort_by = Some(sort_by);
+        self
+    }
+    /// Set the sort order.
+    pub fn sort_order(mut self, sort_order: SortOrder) -> Self {
+        self.query.sort_order = Some(sort_order);
+        self
+    }
+    /// Set the count of results to retrieve.
+    pub fn size(mut self, size: usize) -> Self {
+        self.query.size = Some(size);
+        self
+    }
+    /// Skip the specified count of items.
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.query.offset = Some(offset);
+        self
+    }
+    /// Return the query that would be sent to the server.
+    pub fn into_query(self) -> MediathekQuery {
+        self.query
+    }
+    /// Limit the total count of results that are retrieved by
+    /// [`pages`](Self::pages) and [`items`](Self::items).
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+impl<'client> MediathekQueryBuilder<'client> {
+    /// Build and send the request to the server.
+    pub fn send(self) -> crate::Result<QueryResult> {
+        self.client.send_query(&self.query)
+    }
+
+    /// Send requests to the server until all results have been retrieved,
+    /// yielding one page of results at a time.
+    ///
+    /// See [`crate::MediathekQueryBuilder::pages`].
+    pub fn pages(self) -> Pages<'client> {
+        Pages {
+            client: self.client,
+            pagination: Pagination::new(self.query, self.limit),
+        }
+    }
+
+    /// Send requests to the server until all results have been retrieved,
+    /// yielding one item at a time.
+    ///
+    /// See [`crate::MediathekQueryBuilder::pages`] for details on pagination
+    /// and error handling.
+    pub fn items(self) -> impl Iterator<Item = crate::Result<Item>> + 'client {
+        self.pages().flat_map(|page| match page {
+            Ok(page) => page.results.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        })
+    }
+}
+
+/// An iterator over the pages of a query.
+///
+/// Returned by [`MediathekQueryBuilder::pages`].
+#[derive(Debug)]
+pub struct Pages<'client> {
+    client: &'client Mediathek,
+    pagination: Pagination,
+}
+impl Iterator for Pages<'_> {
+    type Item = crate::Result<QueryResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(page) = self.pagination.next_page() {
+                return Some(Ok(page));
+            }
+            let result = self.client.send_query(self.pagination.next_query()?);
+            if let Some(page) = self.pagination.process(result) {
+                return Some(page);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Mediathek;
+    use crate::{
+        mock::MockServer,
+        models::{MediathekQuery, QueryField},
+        retry::RetryPolicy,
+        Error,
+    };
+
+    #[test]
+    fn test_blocking() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start()?;
+        let mediathek = Mediathek::new_with_url(server.url(), "test".parse().unwrap())?;
+
+        let result = mediathek.query([QueryField::Topic], "livestream").send()?;
+        assert_eq!(result.results.len(), 2);
+
+        let result = mediathek.query_string("#tagesschau >12", false)?.send()?;
+        assert_eq!(result.query_info.total_results, 3);
+
+        let items = mediathek
+            .execute(MediathekQuery::default())
+            .size(3)
+            .limit(7)
+            .items()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(items.len(), 7);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocking_retry() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start()?;
+        let mediathek = Mediathek::builder()
+            .base_url(server.url())
+            .user_agent("test".parse().unwrap())
+            .timeout(Duration::from_secs(10))
+            .retry_policy(
+                RetryPolicy::new()
+                    .max_attempts(2)
+                    .initial_backoff(Duration::from_millis(10)),
+            )
+            .build()?;
+
+        server.fail_next(503, None);
+        let result = mediathek.query([QueryField::Topic], "livestream").send()?;
+        assert_eq!(result.results.len(), 2);
+        assert_eq!(server.request_count(), 2);
+
+        server.fail_next(503, None);
+        server.fail_next(503, None);
+        assert!(matches!(
+            mediathek.query([QueryField::Topic], "livestream").send(),
+            Err(Error::Status(503))
+        ));
+        assert_eq!(server.request_count(), 4);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,424 @@
+//! Caching the results of repeated queries.
+//!
+//! A [`QueryCache`] stores the results of queries sent by a
+//! [`Mediathek`](crate::Mediathek) client in a [`Cache`], keyed by the
+//! serialized [`MediathekQuery`]. A cached result is discarded when
+//!
+//! - it is older than the cache's [TTL](QueryCache::ttl), or
+//! - the server has answered another query using a newer Filmliste
+//!   (see [`QueryInfo::filmliste_timestamp`](crate::models::QueryInfo::filmliste_timestamp)).
+//!
+//! Two caches are provided: [`MemoryCache`] keeps a limited count of results
+//! in memory and evicts the least recently used ones, [`DiskCache`] stores
+//! results as JSON files in a directory so they survive restarts.
+//!
+//! # Example
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use mediathekviewweb::{
+//!     cache::{MemoryCache, QueryCache},
+//!     Mediathek,
+//! };
+//!
+//! fn cached(mediathek: Mediathek) -> Mediathek {
+//!     mediathek.with_cache(QueryCache::new(MemoryCache::new(100)).ttl(Duration::from_secs(60)))
+//! }
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{MediathekQuery, QueryResult};
+
+/// A storage for cached query results.
+///
+/// Keys are the serialized [`MediathekQuery`]s. Implementations should treat
+/// failures (e.g. I/O errors) like missing entries.
+pub trait Cache: Debug + Send + Sync {
+    /// Return the entry stored for a key.
+    fn get(&self, key: &str) -> Option<CacheEntry>;
+    /// Store an entry, replacing a previous entry for the same key.
+    fn insert(&self, key: &str, entry: CacheEntry);
+    /// Remove the entry stored for a key.
+    fn remove(&self, key: &str);
+    /// Remove all entries.
+    fn clear(&self);
+}
+
+/// A cached query result.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CacheEntry {
+    pub result: QueryResult,
+    /// The time when the result was received.
+    pub created: SystemTime,
+}
+
+/// The hit and miss counts of a [`QueryCache`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+impl CacheStats {
+    /// The share of lookups that were answered from the cache, between `0.0`
+    /// and `1.0`.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+/// Caches query results of a [`Mediathek`](crate::Mediathek) client.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct QueryCache {
+    cache: Box<dyn Cache>,
+    ttl: Option<Duration>,
+    /// The latest Filmliste timestamp that has been seen.
+    filmliste_timestamp: AtomicI64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+impl QueryCache {
+    /// Create a query cache that stores results in a [`Cache`].
+    ///
+    /// Results don't expire unless a [TTL](Self::ttl) is set.
+    pub fn new(cache: impl Cache + 'static) -> Self {
+        Self {
+            cache: Box::new(cache),
+            ttl: None,
+            filmliste_timestamp: AtomicI64::new(i64::MIN),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+    /// Discard results that are older than `ttl`.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Return the hit and miss counts of this cache.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Remove all cached results.
+    pub fn clear(&self) {
+        self.cache.clear()
+    }
+
+    /// Return the cached result of a query, counting a hit or a miss.
+    pub(crate) fn get(&self, query: &MediathekQuery) -> Option<QueryResult> {
+        let result = self.lookup(query);
+        match result {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        result
+    }
+
+    fn lookup(&self, query: &MediathekQuery) -> Option<QueryResult> {
+        let key = serde_json::to_string(query).ok()?;
+        let entry = self.cache.get(&key)?;
+
+        let filmliste_timestamp = entry.result.query_info.filmliste_timestamp;
+        let outdated = filmliste_timestamp < self.filmliste_timestamp.load(Ordering::Acquire);
+        let expired = self.ttl.is_some_and(|ttl| {
+            entry
+                .created
+                .elapsed()
+                .map_or(true, |elapsed| elapsed > ttl)
+        });
+        if outdated || expired {
+            self.cache.remove(&key);
+            return None;
+        }
+
+        self.filmliste_timestamp
+            .fetch_max(filmliste_timestamp, Ordering::AcqRel);
+        Some(entry.result)
+    }
+
+    /// Store the result of a query.
+    pub(crate) fn insert(&self, query: &MediathekQuery, result: &QueryResult) {
+        let Ok(key) = serde_json::to_string(query) else {
+            return;
+        };
+        self.filmliste_timestamp
+            .fetch_max(result.query_info.filmliste_timestamp, Ordering::AcqRel);
+        self.cache.insert(
+            &key,
+            CacheEntry {
+                result: result.clone(),
+                created: SystemTime::now(),
+            },
+        );
+    }
+}
+
+/// An in-memory [`Cache`] that evicts the least recently used entries.
+#[derive(Debug)]
+pub struct MemoryCache {
+    capacity: usize,
+    state: Mutex<MemoryCacheState>,
+}
+#[derive(Debug, Default)]
+struct MemoryCacheState {
+    /// The entries and the tick of their last use.
+    entries: HashMap<String, (CacheEntry, u64)>,
+    /// The keys ordered by the tick of their last use.
+    usage: BTreeMap<u64, String>,
+    tick: u64,
+}
+impl MemoryCacheState {
+    fn touch(&mut self, key: &str) {
+        if let Some((_, last_use)) = self.entries.get_mut(key) {
+            self.tick += 1;
+            if let Some(key) = self.usage.remove(last_use) {
+                self.usage.insert(self.tick, key);
+            }
+            *last_use = self.tick;
+        }
+    }
+}
+impl MemoryCache {
+    /// Create a cache that stores at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::default(),
+        }
+    }
+
+    /// The count of stored entries.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+    /// Whether no entries are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MemoryCacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+impl Cache for MemoryCache {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let mut state = self.lock();
+        state.touch(key);
+        state.entries.get(key).map(|(entry, _)| entry.clone())
+    }
+
+    fn insert(&self, key: &str, entry: CacheEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = self.lock();
+        if let Some((previous, _)) = state.entries.get_mut(key) {
+            *previous = entry;
+            state.touch(key);
+            return;
+        }
+        while state.entries.len() >= self.capacity {
+            let Some((_, oldest)) = state.usage.pop_first() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+        }
+        state.tick += 1;
+        let tick = state.tick;
+        state.entries.insert(key.to_owned(), (entry, tick));
+        state.usage.insert(tick, key.to_owned());
+    }
+
+    fn remove(&self, key: &str) {
+        let mut state = self.lock();
+        if let Some((_, last_use)) = state.entries.remove(key) {
+            state.usage.remove(&last_use);
+        }
+    }
+
+    fn clear(&self) {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.usage.clear();
+    }
+}
+
+/// A [`Cache`] that stores each entry as a JSON file in a directory.
+///
+/// File names are derived from a hash of the key, so the directory should not
+/// be used for anything else.
+#[derive(Clone, Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+/// The contents of a cache file.
+#[derive(Deserialize, Serialize)]
+struct DiskCacheFile {
+    key: String,
+    entry: CacheEntry,
+}
+impl DiskCache {
+    /// Create a cache that stores entries in `dir`, creating the directory if
+    /// it doesn't exist.
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.json", crate::fnv1a(key.bytes())))
+    }
+}
+impl Cache for DiskCache {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let file: DiskCacheFile =
+            serde_json::from_slice(&std::fs::read(self.path(key)).ok()?).ok()?;
+        // different keys may have the same hash
+        (file.key == key).then_some(file.entry)
+    }
+
+    fn insert(&self, key: &str, entry: CacheEntry) {
+        let path = self.path(key);
+        let Ok(contents) = serde_json::to_vec(&DiskCacheFile {
+            key: key.to_owned(),
+            entry,
+        }) else {
+            return;
+        };
+        // write to a temporary file first so readers never see partial entries
+        let temp = path.with_extension("json.tmp");
+        if std::fs::write(&temp, contents).is_ok() {
+            let _ = std::fs::rename(&temp, &path);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = std::fs::remove_file(self.path(key));
+    }
+
+    fn clear(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if entry.path().extension().is_some_and(|ext| ext == "json") {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::{Cache, CacheEntry, CacheStats, DiskCache, MemoryCache, QueryCache};
+    use crate::{
+        local::LocalMediathek,
+        mock::fixtures,
+        models::{MediathekQuery, QueryResult},
+    };
+
+    fn query_result(query: &str) -> (MediathekQuery, QueryResult) {
+        let query = MediathekQuery::from_search_string(query, false).unwrap();
+        let result = LocalMediathek::new(fixtures()).execute(&query);
+        (query, result)
+    }
+
+    fn entry(result: QueryResult) -> CacheEntry {
+        CacheEntry {
+            result,
+            created: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn test_memory_cache() {
+        let cache = MemoryCache::new(2);
+        let (_, result) = query_result("#tagesschau");
+
+        cache.insert("a", entry(result.clone()));
+        cache.insert("b", entry(result.clone()));
+        assert!(cache.get("a").is_some());
+        // evicts "b" because "a" has been used more recently
+        cache.insert("c", entry(result.clone()));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+
+        cache.remove("a");
+        assert!(cache.get("a").is_none());
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_disk_cache() {
+        let dir =
+            std::env::temp_dir().join(format!("mediathekviewweb-cache-{}", std::process::id()));
+        let cache = DiskCache::new(&dir).unwrap();
+        let (_, mut result) = query_result("#tagesschau");
+        // the search engine time is serialized with a precision of 10ms
+        result.query_info.search_engine_time = Duration::ZERO;
+
+        cache.insert("a", entry(result.clone()));
+        assert_eq!(cache.get("a").unwrap().result, result);
+        assert!(cache.get("b").is_none());
+
+        // survives restarts
+        let cache = DiskCache::new(&dir).unwrap();
+        assert_eq!(cache.get("a").unwrap().result, result);
+
+        cache.clear();
+        assert!(cache.get("a").is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_query_cache() {
+        let cache = QueryCache::new(MemoryCache::new(10));
+        let (query, mut result) = query_result("#tagesschau");
+        let (other_query, _) = query_result("!zdf");
+
+        assert_eq!(cache.get(&query), None);
+        cache.insert(&query, &result);
+        assert_eq!(cache.get(&query), Some(result.clone()));
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+
+        // a newer Filmliste invalidates older results
+        result.query_info.filmliste_timestamp += 1;
+        cache.insert(&other_query, &result);
+        assert_eq!(cache.get(&query), None);
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2 });
+        assert_eq!(cache.stats().hit_rate(), 1.0 / 3.0);
+
+        let cache = QueryCache::new(MemoryCache::new(10)).ttl(Duration::ZERO);
+        cache.insert(&query, &result);
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(cache.get(&query), None);
+    }
+}
@@ -0,0 +1,184 @@
+//! Calendar calculations for converting between dates and unix timestamps.
+//!
+//! Based on <https://howardhinnant.github.io/date_algorithms.html>.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the number of days since 1970-01-01 for a date in the proleptic
+/// Gregorian calendar.
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Returns the unix timestamp of a date and time in UTC, or `None` if the
+/// date or time doesn't exist or the year is outside of 1 to 9999.
+pub(crate) fn timestamp_from_civil(
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+) -> Option<i64> {
+    if !(1..=9999).contains(&year) || !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    // leap seconds are accepted as the first second of the next minute
+    if days >= days_from_civil(next_year, next_month, 1) || hour > 23 || minute > 59 || second > 60
+    {
+        return None;
+    }
+    Some(days * 86400 + i64::from(hour * 3600 + minute * 60 + second))
+}
+
+/// Returns the year, month and day of a date given as the number of days
+/// since 1970-01-01.
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u32;
+    let month = if month < 10 { month + 3 } else { month - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Returns the UTC offset of the Europe/Berlin time zone in seconds at a unix
+/// timestamp.
+///
+/// Daylight saving time is observed according to the German rules since
+/// 1980. Earlier timestamps always use standard time, which matches the time
+/// zone database from 1950.
+pub(crate) fn berlin_offset(timestamp: i64) -> i32 {
+    const CET: i32 = 60 * 60;
+    const CEST: i32 = 2 * 60 * 60;
+
+    let (year, _, _) = civil_from_days(timestamp.div_euclid(86400));
+    // all transitions happen at 01:00 UTC
+    let start = match year {
+        ..1980 => return CET,
+        1980 => days_from_civil(1980, 4, 6),
+        _ => last_sunday(year, 3),
+    };
+    let end = match year {
+        ..1996 => last_sunday(year, 9),
+        _ => last_sunday(year, 10),
+    };
+    if (start * 86400 + 3600..end * 86400 + 3600).contains(&timestamp) {
+        CEST
+    } else {
+        CET
+    }
+}
+
+/// Returns the last Sunday of a month as the number of days since 1970-01-01.
+fn last_sunday(year: i64, month: u32) -> i64 {
+    let last_day = days_from_civil(year, month + 1, 1) - 1;
+    // 1970-01-01 was a Thursday
+    last_day - (last_day + 4).rem_euclid(7)
+}
+
+/// Returns the unix timestamp of a point in time, rounded up to whole
+/// seconds.
+pub(crate) fn unix_timestamp(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64 + i64::from(duration.subsec_nanos() > 0),
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{
+        berlin_offset, civil_from_days, days_from_civil, timestamp_from_civil, unix_timestamp,
+    };
+
+    #[test]
+    fn test_days_from_civil() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(2023, 10, 23), 19653);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+    }
+
+    #[test]
+    fn test_timestamp_from_civil() {
+        assert_eq!(timestamp_from_civil(1970, 1, 1, 0, 0, 0), Some(0));
+        assert_eq!(
+            timestamp_from_civil(2023, 10, 2, 18, 0, 0),
+            Some(1696269600)
+        );
+        assert_eq!(
+            timestamp_from_civil(2024, 2, 29, 0, 0, 0),
+            Some(days_from_civil(2024, 2, 29) * 86400)
+        );
+        assert_eq!(timestamp_from_civil(2023, 2, 29, 0, 0, 0), None);
+        assert_eq!(timestamp_from_civil(2023, 2, 31, 0, 0, 0), None);
+        assert_eq!(timestamp_from_civil(2023, 13, 1, 0, 0, 0), None);
+        assert_eq!(timestamp_from_civil(2023, 12, 31, 24, 0, 0), None);
+        assert_eq!(timestamp_from_civil(99999999999999999, 1, 1, 0, 0, 0), None);
+        assert!(timestamp_from_civil(9999, 12, 31, 23, 59, 60).is_some());
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        for days in [-719468, -1, 0, 11017, 19653, 2932896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(19653), (2023, 10, 23));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+    }
+
+    #[test]
+    fn test_berlin_offset() {
+        // tagesschau at 20:00 CEST
+        assert_eq!(berlin_offset(1696269600), 2 * 3600);
+        assert_eq!(berlin_offset(1700000000), 3600);
+        // 2023-03-26 01:00 UTC
+        assert_eq!(berlin_offset(1679792400 - 1), 3600);
+        assert_eq!(berlin_offset(1679792400), 2 * 3600);
+        // 2023-10-29 01:00 UTC
+        assert_eq!(berlin_offset(1698541200 - 1), 2 * 3600);
+        assert_eq!(berlin_offset(1698541200), 3600);
+        // 1980-04-06 01:00 UTC
+        assert_eq!(berlin_offset(323830800 - 1), 3600);
+        assert_eq!(berlin_offset(323830800), 2 * 3600);
+        // 1990-09-30 01:00 UTC
+        assert_eq!(berlin_offset(654656400 - 1), 2 * 3600);
+        assert_eq!(berlin_offset(654656400), 3600);
+        assert_eq!(berlin_offset(0), 3600);
+    }
+
+    #[test]
+    fn test_unix_timestamp() {
+        assert_eq!(unix_timestamp(UNIX_EPOCH), 0);
+        assert_eq!(
+            unix_timestamp(UNIX_EPOCH + Duration::from_secs(1696269600)),
+            1696269600
+        );
+        assert_eq!(unix_timestamp(UNIX_EPOCH + Duration::from_millis(1500)), 2);
+        assert_eq!(unix_timestamp(UNIX_EPOCH - Duration::from_millis(1500)), -1);
+    }
+}
@@ -0,0 +1,319 @@
+//! Typed dates and times for the unix timestamps of the API.
+//!
+//! The timestamps of [`Item`] and [`QueryInfo`] are converted to the
+//! Europe/Berlin time zone, so broadcasting dates are shown as announced by
+//! the broadcasters, including daylight saving time. The supported types
+//! depend on the enabled features:
+//!
+//! | Feature  | Type                                 |
+//! |----------|--------------------------------------|
+//! |          | [`BerlinTime`]                       |
+//! | `time`   | `time::OffsetDateTime`               |
+//! | `chrono` | `chrono::DateTime<chrono_tz::Tz>`    |
+//! | `jiff`   | `jiff::Zoned`                        |
+//!
+//! All types support the same range of timestamps, from
+//! `1950-01-01T00:00:00Z` to `9999-12-30T22:00:00Z`, the end of the range of
+//! `jiff::Timestamp`. [`BerlinTime`] and `time::OffsetDateTime` use built-in
+//! daylight saving time rules, which match the time zone database of the
+//! other types in this range. [`FromTimestamp::from_timestamp`] returns
+//! `None` for timestamps out of the range.
+//!
+//! # Example
+//! ```rust
+//! # #[cfg(feature = "jiff")]
+//! # {
+//! use mediathekviewweb::{datetime::FromTimestamp, models::Item};
+//!
+//! fn broadcast_date(item: &Item) -> Option<String> {
+//!     let date = item.timestamp_as::<jiff::Zoned>()?;
+//!     Some(date.strftime("%d.%m.%Y %H:%M").to_string())
+//! }
+//!
+//! let date = jiff::Zoned::from_timestamp(1696269600).unwrap();
+//! assert_eq!(date.to_string(), "2023-10-02T20:00:00+02:00[Europe/Berlin]");
+//! # }
+//! ```
+
+use std::fmt::Display;
+
+use crate::{
+    date,
+    models::{Item, QueryInfo},
+};
+
+/// A date and time type that a unix timestamp can be converted to.
+pub trait FromTimestamp: Sized {
+    /// Convert a unix timestamp to a date and time in the Europe/Berlin time
+    /// zone.
+    ///
+    /// Returns `None` if the timestamp is out of the
+    /// [supported range](self).
+    fn from_timestamp(timestamp: i64) -> Option<Self>;
+}
+
+/// A date and time in the Europe/Berlin time zone that doesn't depend on a
+/// date and time library.
+///
+/// It is formatted as specified by RFC 3339, e.g.
+/// `2023-10-02T20:00:00+02:00`.
+///
+/// # Example
+/// ```rust
+/// use mediathekviewweb::datetime::{BerlinTime, FromTimestamp};
+///
+/// let date = BerlinTime::from_timestamp(1696269600).unwrap();
+/// assert_eq!((date.day, date.month, date.year), (2, 10, 2023));
+/// assert_eq!(date.to_string(), "2023-10-02T20:00:00+02:00");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BerlinTime {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// The offset from UTC in seconds, i.e. `3600` for CET and `7200` for
+    /// CEST.
+    pub offset: i32,
+}
+impl BerlinTime {
+    /// The earliest supported timestamp, `1950-01-01T00:00:00Z`.
+    const MIN: i64 = -631152000;
+    /// The latest supported timestamp, `9999-12-30T22:00:00Z`.
+    const MAX: i64 = 253402207200;
+
+    /// Check whether a timestamp is in the supported range.
+    fn supports(timestamp: i64) -> bool {
+        (Self::MIN..=Self::MAX).contains(&timestamp)
+    }
+
+    /// Convert a timestamp, using the earliest or latest supported time for
+    /// timestamps out of the supported range.
+    pub(crate) fn saturating(timestamp: i64) -> Self {
+        let timestamp = timestamp.clamp(Self::MIN, Self::MAX);
+        let offset = date::berlin_offset(timestamp);
+        let local = timestamp + i64::from(offset);
+        let (year, month, day) = date::civil_from_days(local.div_euclid(86400));
+        let seconds = local.rem_euclid(86400);
+        Self {
+            year: year as i32,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+            offset,
+        }
+    }
+
+    /// Return the day of the week, from `1` for Monday to `7` for Sunday.
+    pub fn weekday(&self) -> u8 {
+        let days = date::days_from_civil(self.year.into(), self.month.into(), self.day.into());
+        // 1970-01-01 was a Thursday
+        (days + 3).rem_euclid(7) as u8 + 1
+    }
+}
+impl FromTimestamp for BerlinTime {
+    fn from_timestamp(timestamp: i64) -> Option<Self> {
+        Self::supports(timestamp).then(|| Self::saturating(timestamp))
+    }
+}
+impl Display for BerlinTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}+{:02}:{:02}",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            self.offset / 3600,
+            self.offset / 60 % 60
+        )
+    }
+}
+
+#[cfg(feature = "time")]
+impl FromTimestamp for time::OffsetDateTime {
+    fn from_timestamp(timestamp: i64) -> Option<Self> {
+        if !BerlinTime::supports(timestamp) {
+            return None;
+        }
+        let offset = time::UtcOffset::from_whole_seconds(date::berlin_offset(timestamp)).ok()?;
+        time::OffsetDateTime::from_unix_timestamp(timestamp)
+            .ok()?
+            .checked_to_offset(offset)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl FromTimestamp for chrono::DateTime<chrono_tz::Tz> {
+    fn from_timestamp(timestamp: i64) -> Option<Self> {
+        if !BerlinTime::supports(timestamp) {
+            return None;
+        }
+        Some(
+            chrono::DateTime::from_timestamp(timestamp, 0)?
+                .with_timezone(&chrono_tz::Europe::Berlin),
+        )
+    }
+}
+
+#[cfg(feature = "jiff")]
+impl FromTimestamp for jiff::Zoned {
+    fn from_timestamp(timestamp: i64) -> Option<Self> {
+        if !BerlinTime::supports(timestamp) {
+            return None;
+        }
+        let time_zone = jiff::tz::TimeZone::get("Europe/Berlin").ok()?;
+        Some(
+            jiff::Timestamp::from_second(timestamp)
+                .ok()?
+                .to_zoned(time_zone),
+        )
+    }
+}
+
+impl Item {
+    /// Return the broadcasting date in the Europe/Berlin time zone.
+    ///
+    /// See the [module documentation](self) for the supported types.
+    pub fn timestamp_as<T: FromTimestamp>(&self) -> Option<T> {
+        T::from_timestamp(self.timestamp)
+    }
+
+    /// Return the creation date of the Filmliste that contained this item in
+    /// the Europe/Berlin time zone.
+    pub fn filmliste_timestamp_as<T: FromTimestamp>(&self) -> Option<T> {
+        T::from_timestamp(self.filmliste_timestamp)
+    }
+}
+
+impl QueryInfo {
+    /// Return the creation date of the Filmliste that was searched in the
+    /// Europe/Berlin time zone.
+    pub fn filmliste_timestamp_as<T: FromTimestamp>(&self) -> Option<T> {
+        T::from_timestamp(self.filmliste_timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BerlinTime, FromTimestamp};
+    use crate::mock::fixtures;
+
+    #[test]
+    fn test_berlin_time() {
+        let items = fixtures();
+        let broadcast = items[0].timestamp_as::<BerlinTime>().unwrap();
+        assert_eq!(broadcast.to_string(), "2023-10-02T20:00:00+02:00");
+        assert_eq!(broadcast.weekday(), 1);
+
+        let winter = BerlinTime::from_timestamp(1700000000).unwrap();
+        assert_eq!(winter.to_string(), "2023-11-14T23:13:20+01:00");
+        assert_eq!(winter.weekday(), 2);
+        assert_eq!(
+            BerlinTime::from_timestamp(0).unwrap().to_string(),
+            "1970-01-01T01:00:00+01:00"
+        );
+
+        assert!(BerlinTime::from_timestamp(i64::MAX).is_none());
+        assert_eq!(
+            BerlinTime::from_timestamp(BerlinTime::MIN)
+                .unwrap()
+                .to_string(),
+            "1950-01-01T01:00:00+01:00"
+        );
+        assert!(BerlinTime::from_timestamp(BerlinTime::MIN - 1).is_none());
+        assert_eq!(
+            BerlinTime::from_timestamp(BerlinTime::MAX)
+                .unwrap()
+                .to_string(),
+            "9999-12-30T23:00:00+01:00"
+        );
+        assert!(BerlinTime::from_timestamp(BerlinTime::MAX + 1).is_none());
+        assert_eq!(BerlinTime::saturating(i64::MAX).year, 9999);
+        assert_eq!(BerlinTime::saturating(i64::MIN).year, 1950);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn test_time() {
+        let items = fixtures();
+        let broadcast = items[0].timestamp_as::<time::OffsetDateTime>().unwrap();
+        assert_eq!((broadcast.hour(), broadcast.minute()), (20, 0));
+        assert_eq!(broadcast.offset().whole_hours(), 2);
+
+        let broadcast = time::OffsetDateTime::from_timestamp(1700000000).unwrap();
+        assert_eq!((broadcast.hour(), broadcast.minute()), (23, 13));
+        assert_eq!(broadcast.offset().whole_hours(), 1);
+
+        assert!(time::OffsetDateTime::from_timestamp(i64::MAX).is_none());
+        assert!(time::OffsetDateTime::from_timestamp(BerlinTime::MAX).is_some());
+        assert!(time::OffsetDateTime::from_timestamp(BerlinTime::MIN - 1).is_none());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use chrono::Timelike;
+
+        let items = fixtures();
+        let broadcast = items[0]
+            .timestamp_as::<chrono::DateTime<chrono_tz::Tz>>()
+            .unwrap();
+        assert_eq!(broadcast.to_rfc3339(), "2023-10-02T20:00:00+02:00");
+        let filmliste = items[0]
+            .filmliste_timestamp_as::<chrono::DateTime<chrono_tz::Tz>>()
+            .unwrap();
+        assert_eq!(filmliste.hour(), 15);
+
+        assert!(chrono::DateTime::<chrono_tz::Tz>::from_timestamp(i64::MAX).is_none());
+        assert!(chrono::DateTime::<chrono_tz::Tz>::from_timestamp(BerlinTime::MAX).is_some());
+        assert!(chrono::DateTime::<chrono_tz::Tz>::from_timestamp(BerlinTime::MIN - 1).is_none());
+    }
+
+    #[cfg(feature = "jiff")]
+    #[test]
+    fn test_jiff() {
+        let items = fixtures();
+        let broadcast = items[0].timestamp_as::<jiff::Zoned>().unwrap();
+        assert_eq!(
+            broadcast.to_string(),
+            "2023-10-02T20:00:00+02:00[Europe/Berlin]"
+        );
+        let winter = jiff::Zoned::from_timestamp(1700000000).unwrap();
+        assert_eq!(winter.offset(), jiff::tz::offset(1));
+
+        assert!(jiff::Zoned::from_timestamp(i64::MAX).is_none());
+        assert!(jiff::Zoned::from_timestamp(BerlinTime::MAX).is_some());
+        assert!(jiff::Zoned::from_timestamp(BerlinTime::MIN - 1).is_none());
+    }
+
+    /// The built-in rules match the time zone database in the supported
+    /// range.
+    #[cfg(feature = "jiff")]
+    #[test]
+    fn test_berlin_offset() {
+        let time_zone = jiff::tz::TimeZone::get("Europe/Berlin").unwrap();
+        // every 3 hours from 1950 until 2100, then every 5 days and 1 hour
+        let timestamps = (BerlinTime::MIN..4102444800)
+            .step_by(3 * 60 * 60)
+            .chain((4102444800..=BerlinTime::MAX).step_by(121 * 60 * 60))
+            .chain([BerlinTime::MAX]);
+        for timestamp in timestamps {
+            let expected = time_zone
+                .to_offset(jiff::Timestamp::from_second(timestamp).unwrap())
+                .seconds();
+            assert_eq!(
+                crate::date::berlin_offset(timestamp),
+                expected,
+                "{timestamp}"
+            );
+        }
+    }
+}
@@ -0,0 +1,544 @@
+//! Downloading the videos of [`Item`]s.
+//!
+//! A [`Downloader`] saves a video to a file. While downloading, the data is
+//! written to a temporary file next to the target file, whose name ends with
+//! `.part`. If a download is interrupted, the next download of the same video
+//! to the same path resumes from the temporary file using an HTTP range
+//! request. The `ETag` or `Last-Modified` header of the video is saved in a
+//! second file ending with `.part.validator` and sent as `If-Range`, so the
+//! download starts over if the video has changed in the meantime. After the
+//! download has been completed and verified, the temporary file is renamed to
+//! the target path, so the target file never contains an incomplete video. If
+//! the verification fails, the temporary file is deleted.
+//!
+//! # Example
+//! ```rust,no_run
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> mediathekviewweb::Result<()> {
+//! # #[allow(non_snake_case)]
+//! # let USER_AGENT = "example".try_into().unwrap();
+//! # let item: mediathekviewweb::models::Item = todo!();
+//! use mediathekviewweb::{download::Downloader, models::Quality};
+//!
+//! let downloader = Downloader::new(USER_AGENT)?.max_concurrent(4);
+//! downloader
+//!     .download(&item, Quality::Hd, "video.mp4", |progress| {
+//!         if let Some(total) = progress.total {
+//!             println!("{} / {total} bytes", progress.downloaded);
+//!         }
+//!     })
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tokio::{io::AsyncWriteExt, sync::Semaphore};
+
+use crate::{
+    hls::{Playlist, PlaylistError, VariantSelection},
+    models::{Item, Quality},
+    Error,
+};
+
+/// The allowed difference between the size of a downloaded video and
+/// [`Item::size`], which is rounded to whole mebibytes.
+pub const SIZE_TOLERANCE: u64 = 1024 * 1024;
+
+/// The progress of a download, passed to the progress callback of
+/// [`Downloader::download`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// The count of bytes that have been downloaded, including the bytes of a
+    /// resumed download.
+    pub downloaded: u64,
+    /// The total size of the video, if known.
+    pub total: Option<u64>,
+}
+
+/// Downloads videos with a limited count of concurrent downloads.
+///
+/// Clones share the limit of concurrent downloads.
+#[derive(Clone, Debug)]
+pub struct Downloader {
+    http: reqwest::Client,
+    permits: Arc<Semaphore>,
+}
+impl Downloader {
+    /// The count of concurrent downloads if no limit is set.
+    pub const DEFAULT_MAX_CONCURRENT: usize = 2;
+
+    /// Create a new downloader.
+    ///
+    /// `user_agent` identifies your application, see
+    /// [`Mediathek::new`](crate::Mediathek::new).
+    pub fn new(user_agent: reqwest::header::HeaderValue) -> crate::Result<Self> {
+        Ok(Self::with_client(
+            reqwest::Client::builder()
+                .default_headers({
+                    let mut headers = reqwest::header::HeaderMap::new();
+                    headers.insert(reqwest::header::USER_AGENT, user_agent);
+                    headers
+                })
+                .build()?,
+        ))
+    }
+
+    /// Create a new downloader that uses an existing [`reqwest::Client`].
+    pub fn with_client(http: reqwest::Client) -> Self {
+        Self {
+            http,
+            permits: Arc::new(Semaphore::new(Self::DEFAULT_MAX_CONCURRENT)),
+        }
+    }
+
+    /// Limit the count of concurrent downloads. Further downloads wait until a
+    /// running download has finished.
+    pub fn max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.permits = Arc::new(Semaphore::new(max_concurrent.max(1)));
+        self
+    }
+
+    /// Download the video of an item in a specific quality to `path`.
+    ///
+    /// Use [`Item::select_url`] to choose an available quality.
+    ///
+    /// `progress` is called after each received chunk of data. For
+    /// [`Quality::Normal`], the size of the downloaded file is compared to
+    /// [`Item::size`] with a tolerance of [`SIZE_TOLERANCE`].
+    ///
+    /// Returns [`Error::QualityUnavailable`] if the item has no video in the
+    /// requested quality and [`Error::SizeMismatch`] if the downloaded file has
+    /// an unexpected size. In that case, the temporary file is deleted, so the
+    /// next download starts over.
+    pub async fn download(
+        &self,
+        item: &Item,
+        quality: Quality,
+        path: impl AsRef<Path>,
+        progress: impl FnMut(Progress) + Send,
+    ) -> crate::Result<()> {
+        let url = item
+            .url_for(quality)
+            .ok_or(Error::QualityUnavailable(quality))?;
+        let expected_size = match quality {
+            Quality::Normal => item.size.map(|size| size as u64),
+            _ => None,
+        };
+        self.download_url(url, expected_size, path, progress).await
+    }
+
+    /// Download a file from `url` to `path`.
+    ///
+    /// See [`download`](Self::download) for details. If `expected_size` is
+    /// set, the size of the downloaded file is compared to it with a tolerance
+    /// of [`SIZE_TOLERANCE`].
+    pub async fn download_url(
+        &self,
+        url: &str,
+        expected_size: Option<u64>,
+        path: impl AsRef<Path>,
+        mut progress: impl FnMut(Progress) + Send,
+    ) -> crate::Result<()> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("semaphore is never closed");
+
+        let path = path.as_ref();
+        let part = part_path(path);
+        let validator_path = validator_path(path);
+        let mut downloaded = match tokio::fs::metadata(&part).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut request = self.http.get(url);
+        if downloaded > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={downloaded}-"));
+            if let Ok(validator) = tokio::fs::read_to_string(&validator_path).await {
+                request = request.header(reqwest::header::IF_RANGE, validator);
+            }
+        }
+        let mut response = request.send().await?;
+
+        let total = match response.status().as_u16() {
+            // the server ignores the range or the video has changed, so start
+            // from the beginning
+            200 => {
+                downloaded = 0;
+                match validator(&response) {
+                    Some(validator) => tokio::fs::write(&validator_path, validator).await?,
+                    None => remove_if_exists(&validator_path).await?,
+                }
+                response.content_length()
+            }
+            206 => content_range_total(&response),
+            // the temporary file already contains the entire file
+            416 if downloaded > 0 => content_range_total(&response),
+            status => return Err(Error::Status(status)),
+        };
+        progress(Progress { downloaded, total });
+
+        if response.status().is_success() {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(downloaded > 0)
+                .truncate(downloaded == 0)
+                .open(&part)
+                .await?;
+            while let Some(chunk) = response.chunk().await? {
+                file.write_all(&chunk).await?;
+                downloaded += chunk.len() as u64;
+                progress(Progress { downloaded, total });
+            }
+            file.sync_all().await?;
+        }
+
+        let actual = tokio::fs::metadata(&part).await?.len();
+        let mismatch = match total {
+            Some(total) if actual != total => Some(total),
+            _ => expected_size.filter(|expected| actual.abs_diff(*expected) > SIZE_TOLERANCE),
+        };
+        if let Some(expected) = mismatch {
+            // resuming from a broken temporary file would fail again, e.g.
+            // with `416` if it is longer than the video
+            tokio::fs::remove_file(&part).await?;
+            remove_if_exists(&validator_path).await?;
+            return Err(Error::SizeMismatch { expected, actual });
+        }
+
+        tokio::fs::rename(&part, path).await?;
+        remove_if_exists(&validator_path).await?;
+        Ok(())
+    }
+
+    /// Download an HLS stream from `url` to `path` by concatenating its
+    /// segments.
+    ///
+    /// If `url` points to a master playlist, a variant is chosen using
+    /// `selection`. Renditions with a separate playlist, e.g. alternative
+    /// audio tracks, are not included. For livestreams, only the segments that
+    /// are currently listed in the playlist are downloaded.
+    ///
+    /// Unlike [`download`](Self::download), interrupted downloads are not
+    /// resumed. The total size passed to `progress` is always `None`.
+    ///
+    /// Returns [`Error::VariantUnavailable`] if no variant matches
+    /// `selection` and [`Error::EncryptedStream`] if the segments are
+    /// encrypted.
+    pub async fn download_hls(
+        &self,
+        url: &str,
+        selection: VariantSelection,
+        path: impl AsRef<Path>,
+        mut progress: impl FnMut(Progress) + Send,
+    ) -> crate::Result<()> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("semaphore is never closed");
+
+        let (playlist, mut base) = self.fetch_playlist(url).await?;
+        let media = match playlist {
+            Playlist::Media(media) => media,
+            Playlist::Master(master) => {
+                let variant = master.select(selection).ok_or(Error::VariantUnavailable)?;
+                let (playlist, variant_base) =
+                    self.fetch_playlist(&resolve(&base, &variant.uri)).await?;
+                base = variant_base;
+                match playlist {
+                    Playlist::Media(media) => media,
+                    Playlist::Master(_) => {
+                        return Err(PlaylistError {
+                            line: 1,
+                            message: "variant is a master playlist".into(),
+                        }
+                        .into())
+                    }
+                }
+            }
+        };
+        if media.encrypted {
+            return Err(Error::EncryptedStream);
+        }
+
+        let path = path.as_ref();
+        let part = part_path(path);
+        let mut file = tokio::fs::File::create(&part).await?;
+        let mut downloaded = 0;
+        progress(Progress {
+            downloaded,
+            total: None,
+        });
+
+        let uris = media
+            .init_segment
+            .iter()
+            .chain(media.segments.iter().map(|segment| &segment.uri));
+        for uri in uris {
+            let mut response = self.http.get(resolve(&base, uri)).send().await?;
+            if !response.status().is_success() {
+                return Err(Error::Status(response.status().as_u16()));
+            }
+            while let Some(chunk) = response.chunk().await? {
+                file.write_all(&chunk).await?;
+                downloaded += chunk.len() as u64;
+                progress(Progress {
+                    downloaded,
+                    total: None,
+                });
+            }
+        }
+        file.sync_all().await?;
+
+        tokio::fs::rename(&part, path).await?;
+        Ok(())
+    }
+
+    /// Download and parse a playlist, returning it with its final URL after
+    /// redirects.
+    async fn fetch_playlist(&self, url: &str) -> crate::Result<(Playlist, reqwest::Url)> {
+        let response = self.http.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(Error::Status(response.status().as_u16()));
+        }
+        let url = response.url().clone();
+        Ok((response.text().await?.parse()?, url))
+    }
+}
+
+/// Resolve a URI of a playlist relative to the playlist's URL.
+fn resolve(base: &reqwest::Url, uri: &str) -> String {
+    // invalid URIs are reported when they are requested
+    base.join(uri).map_or_else(|_| uri.to_owned(), String::from)
+}
+
+/// Return the path of the temporary file for a download to `path`.
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    part.into()
+}
+
+/// Return the path of the file that stores the validator of a download to
+/// `path`.
+fn validator_path(path: &Path) -> PathBuf {
+    let mut validator = path.as_os_str().to_owned();
+    validator.push(".part.validator");
+    validator.into()
+}
+
+/// Return the value for an `If-Range` header that identifies the version of a
+/// video: its `ETag` if it is a strong one, or its `Last-Modified` date.
+fn validator(response: &reqwest::Response) -> Option<&str> {
+    let headers = response.headers();
+    headers
+        .get(reqwest::header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| headers.get(reqwest::header::LAST_MODIFIED)?.to_str().ok())
+}
+
+/// Remove a file, ignoring that it doesn't exist.
+async fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Parse the total size from a `Content-Range` header like
+/// `bytes 100-199/200`.
+fn content_range_total(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .rsplit_once('/')?
+        .1
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{part_path, validator_path, Downloader, Progress};
+    use crate::{
+        hls::VariantSelection,
+        mock::{fixtures, MockServer},
+        models::{Item, Quality},
+        Error,
+    };
+
+    fn item(server: &MockServer, size: usize) -> Item {
+        Item {
+            size: Some(size),
+            url_video: format!("{}/video.mp4", server.url()),
+            url_video_hd: None,
+            ..fixtures().remove(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start()?;
+        let video = (0..3 * 1024 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        server.serve_file("/video.mp4", video.clone());
+
+        let dir =
+            std::env::temp_dir().join(format!("mediathekviewweb-download-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("video.mp4");
+
+        // resume an interrupted download
+        std::fs::write(part_path(&path), &video[..1000])?;
+        let downloader = Downloader::with_client(reqwest::Client::new());
+        let mut updates = Vec::new();
+        downloader
+            .download(&item(&server, video.len()), Quality::Normal, &path, |p| {
+                updates.push(p)
+            })
+            .await?;
+        assert_eq!(std::fs::read(&path)?, video);
+        assert!(!part_path(&path).exists());
+        assert_eq!(
+            updates.first(),
+            Some(&Progress {
+                downloaded: 1000,
+                total: Some(video.len() as u64)
+            })
+        );
+        assert_eq!(updates.last().unwrap().downloaded, video.len() as u64);
+        assert!(!validator_path(&path).exists());
+
+        // start over if the video has changed since the download started
+        std::fs::write(part_path(&path), [0; 1000])?;
+        std::fs::write(validator_path(&path), r#""outdated""#)?;
+        let mut updates = Vec::new();
+        downloader
+            .download(&item(&server, video.len()), Quality::Normal, &path, |p| {
+                updates.push(p)
+            })
+            .await?;
+        assert_eq!(std::fs::read(&path)?, video);
+        assert_eq!(updates.first().unwrap().downloaded, 0);
+        assert!(!validator_path(&path).exists());
+
+        // a temporary file that is longer than the video is deleted
+        std::fs::write(part_path(&path), vec![0; video.len() + 1])?;
+        assert!(matches!(
+            downloader
+                .download(&item(&server, video.len()), Quality::Normal, &path, |_| {})
+                .await,
+            Err(Error::SizeMismatch { .. })
+        ));
+        assert!(!part_path(&path).exists());
+
+        assert!(matches!(
+            downloader
+                .download(&item(&server, video.len()), Quality::Hd, &path, |_| {})
+                .await,
+            Err(Error::QualityUnavailable(Quality::Hd))
+        ));
+        assert!(matches!(
+            downloader
+                .download(
+                    &item(&server, 10 * 1024 * 1024),
+                    Quality::Normal,
+                    &path,
+                    |_| {}
+                )
+                .await,
+            Err(Error::SizeMismatch { .. })
+        ));
+        assert!(!part_path(&path).exists());
+        assert!(!validator_path(&path).exists());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_hls() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start()?;
+        server.serve_file(
+            "/hls/master.m3u8",
+            "#EXTM3U
+#EXT-X-STREAM-INF:BANDWIDTH=1000000,RESOLUTION=640x360
+low/index.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080
+high/index.m3u8
+",
+        );
+        server.serve_file(
+            "/hls/high/index.m3u8",
+            "#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXTINF:6.0,
+segment0.ts
+#EXTINF:6.0,
+/hls/high/segment1.ts
+#EXT-X-ENDLIST
+",
+        );
+        server.serve_file("/hls/high/segment0.ts", "first segment, ");
+        server.serve_file("/hls/high/segment1.ts", "second segment");
+
+        let dir = std::env::temp_dir().join(format!("mediathekviewweb-hls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("stream.ts");
+
+        let downloader = Downloader::with_client(reqwest::Client::new());
+        let mut downloaded = 0;
+        downloader
+            .download_hls(
+                &format!("{}/hls/master.m3u8", server.url()),
+                VariantSelection::Highest,
+                &path,
+                |progress| downloaded = progress.downloaded,
+            )
+            .await?;
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "first segment, second segment"
+        );
+        assert_eq!(downloaded, 29);
+
+        // the low quality variant doesn't exist
+        assert!(matches!(
+            downloader
+                .download_hls(
+                    &format!("{}/hls/master.m3u8", server.url()),
+                    VariantSelection::Lowest,
+                    &path,
+                    |_| {},
+                )
+                .await,
+            Err(Error::Status(404))
+        ));
+        assert!(matches!(
+            downloader
+                .download_hls(
+                    &format!("{}/hls/master.m3u8", server.url()),
+                    VariantSelection::MaxHeight(240),
+                    &path,
+                    |_| {},
+                )
+                .await,
+            Err(Error::VariantUnavailable)
+        ));
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+//! Client-side emulation of the parts of a [`MediathekQuery`] that the server
+//! doesn't support, i.e. [`exclude`](MediathekQuery::exclude)d queries,
+//! [`any`](MediathekQuery::any) groups, exact
+//! [`channels`](MediathekQuery::channels) and the broadcast window.
+//!
+//! Every combination of the alternatives of the `any` groups and the channels
+//! is sent as a separate query, starting at offset 0 and retrieving pages until enough
+//! results that pass the client-side filters have been collected to fill the
+//! requested page. The results of all combinations are then merged,
+//! de-duplicated by their ID and sorted like the server would sort them. If no
+//! sort field is set, the results of the combinations are concatenated.
+//!
+//! When such a query is paginated, every combination keeps its offset across
+//! pages and the results are merged incrementally by [`Merge`], so each result
+//! is only requested once.
+
+use std::{
+    cmp::Ordering,
+    collections::{HashSet, VecDeque},
+    mem,
+    time::Duration,
+};
+
+use crate::{
+    local::{self, DEFAULT_SIZE},
+    models::{Channel, Item, MediathekQuery, QueryInfo, QueryResult, SortField, SortOrder},
+};
+
+impl MediathekQuery {
+    /// Check whether this query uses features that the server doesn't
+    /// support.
+    pub(crate) fn needs_emulation(&self) -> bool {
+        !self.exclude.is_empty()
+            || !self.any.is_empty()
+            || !self.channels.is_empty()
+            || self.broadcast_after.is_some()
+            || self.broadcast_before.is_some()
+    }
+
+    /// Return this query without the parts that are applied by the client.
+    pub(crate) fn server_query(&self) -> MediathekQuery {
+        MediathekQuery {
+            exclude: Vec::new(),
+            any: Vec::new(),
+            channels: Vec::new(),
+            broadcast_after: None,
+            broadcast_before: None,
+            ..self.clone()
+        }
+    }
+
+    /// Return the queries that are sent to the server, one for every
+    /// combination of the alternatives of the `any` groups and the channels.
+    pub(crate) fn server_queries(&self) -> Vec<MediathekQuery> {
+        let channels = self.channels.iter().map(Channel::query).collect::<Vec<_>>();
+        let groups = self
+            .any
+            .iter()
+            .chain(Some(&channels).filter(|channels| !channels.is_empty()));
+
+        let mut queries = vec![self.server_query()];
+        for group in groups {
+            queries = queries
+                .iter()
+                .flat_map(|query| {
+                    group.iter().map(|alternative| {
+                        let mut query = query.clone();
+                        query.queries.push(alternative.clone());
+                        query
+                    })
+                })
+                .collect();
+        }
+        queries
+    }
+
+    /// Check whether an item passes the filters that are applied by the
+    /// client to the results of the [`server_query`](Self::server_query).
+    ///
+    /// The `any` groups are not checked because they are part of the
+    /// [`server_queries`](Self::server_queries).
+    pub(crate) fn accepts(&self, item: &Item) -> bool {
+        self.in_broadcast_window(item)
+            && self.has_channel(item)
+            && !self.exclude.iter().any(|query| query.matches(item))
+    }
+
+    /// Check whether the results are sorted by timestamp and this item comes
+    /// after the broadcast window, so no further results can be inside the
+    /// window.
+    pub(crate) fn is_past_broadcast_window(&self, item: &Item) -> bool {
+        match (
+            self.sort_by,
+            self.sort_order.unwrap_or(SortOrder::Descending),
+        ) {
+            (Some(SortField::Timestamp), SortOrder::Descending) => self
+                .broadcast_after
+                .is_some_and(|after| item.timestamp < after),
+            (Some(SortField::Timestamp), SortOrder::Ascending) => self
+                .broadcast_before
+                .is_some_and(|before| item.timestamp >= before),
+            _ => false,
+        }
+    }
+}
+
+/// The state of an emulated query.
+#[derive(Debug)]
+pub(crate) struct Emulation {
+    query: MediathekQuery,
+    branches: Vec<Branch>,
+    /// The index of the branch whose results are currently retrieved.
+    current: usize,
+    filmliste_timestamp: i64,
+    search_engine_time: Duration,
+}
+
+/// One of the queries that are sent to the server.
+#[derive(Debug)]
+struct Branch {
+    query: MediathekQuery,
+    /// The retrieved results that pass the client-side filters.
+    results: VecDeque<Item>,
+    total_results: u64,
+    /// Whether all results of this query have been retrieved.
+    exhausted: bool,
+}
+impl Branch {
+    /// Create the branches of a query, each retrieving `size` results per
+    /// request.
+    fn all(query: &MediathekQuery, size: usize) -> Vec<Self> {
+        query
+            .server_queries()
+            .into_iter()
+            .map(|server_query| Branch {
+                query: MediathekQuery {
+                    size: Some(size),
+                    offset: None,
+                    ..server_query
+                },
+                results: VecDeque::new(),
+                total_results: 0,
+                exhausted: false,
+            })
+            .collect()
+    }
+
+    /// Process the response to the query of this branch, keeping the results
+    /// that pass the client-side filters of `filters`.
+    fn process(&mut self, filters: &MediathekQuery, result: QueryResult) {
+        let received = result.results.len();
+        let offset = self.query.offset.unwrap_or(0) + received;
+        self.query.offset = Some(offset);
+        self.total_results = result.query_info.total_results;
+        self.exhausted = received == 0
+            || offset as u64 >= self.total_results
+            || result
+                .results
+                .iter()
+                .any(|item| filters.is_past_broadcast_window(item));
+        self.results.extend(
+            result
+                .results
+                .into_iter()
+                .filter(|item| filters.accepts(item)),
+        );
+    }
+}
+
+impl Emulation {
+    pub(crate) fn new(query: &MediathekQuery) -> Self {
+        Self {
+            query: query.clone(),
+            branches: Branch::all(query, wanted(query)),
+            current: 0,
+            filmliste_timestamp: 0,
+            search_engine_time: Duration::ZERO,
+        }
+    }
+
+    /// Return the next query that should be sent to the server or `None` if
+    /// enough results have been retrieved.
+    pub(crate) fn next_query(&self) -> Option<&MediathekQuery> {
+        self.branches.get(self.current).map(|branch| &branch.query)
+    }
+
+    /// Process the response to the query returned by
+    /// [`next_query`](Self::next_query).
+    pub(crate) fn process(&mut self, result: QueryResult) {
+        let wanted = wanted(&self.query);
+        let branch = &mut self.branches[self.current];
+
+        self.filmliste_timestamp = self
+            .filmliste_timestamp
+            .max(result.query_info.filmliste_timestamp);
+        self.search_engine_time += result.query_info.search_engine_time;
+
+        branch.process(&self.query, result);
+        if branch.exhausted || branch.results.len() >= wanted {
+            self.current += 1;
+        }
+    }
+
+    /// Merge the retrieved results into the requested page.
+    ///
+    /// The total count of results is exact if all results of all queries have
+    /// been retrieved. Otherwise, it is the sum of the totals reported by the
+    /// server, which is an upper bound.
+    pub(crate) fn finish(self) -> QueryResult {
+        let exhausted = self.branches.iter().all(|branch| branch.exhausted);
+        let upper_bound = self
+            .branches
+            .iter()
+            .map(|branch| branch.total_results)
+            .sum::<u64>();
+
+        let mut ids = HashSet::new();
+        let mut results = self
+            .branches
+            .into_iter()
+            .flat_map(|branch| branch.results)
+            .filter(|item| ids.insert(item.id.clone()))
+            .collect::<Vec<_>>();
+        if self.query.sort_by.is_some() {
+            results.sort_by(|a, b| compare(&self.query, a, b));
+        }
+
+        let total_results = if exhausted {
+            results.len() as u64
+        } else {
+            upper_bound
+        };
+        let results = results
+            .into_iter()
+            .skip(self.query.offset.unwrap_or(0))
+            .take(page_size(&self.query))
+            .collect::<Vec<_>>();
+
+        QueryResult {
+            query_info: QueryInfo {
+                filmliste_timestamp: self.filmliste_timestamp,
+                result_count: results.len(),
+                search_engine_time: self.search_engine_time,
+                total_results,
+            },
+            results,
+        }
+    }
+}
+
+/// The state of an emulated query that is retrieved page by page.
+///
+/// Unlike [`Emulation`], which starts every query at offset 0, each branch
+/// keeps its offset across pages and only the results that are needed to
+/// decide which result comes next are requested.
+#[derive(Debug)]
+pub(crate) struct Merge {
+    query: MediathekQuery,
+    branches: Vec<Branch>,
+    /// The IDs of the merged results, to skip results that are returned by
+    /// several queries.
+    ids: HashSet<String>,
+    /// The count of merged results that are still skipped because of the
+    /// offset of the query.
+    skip: usize,
+    /// The merged results of the next page.
+    page: Vec<Item>,
+    filmliste_timestamp: i64,
+    /// The time spent by the server since the last page.
+    search_engine_time: Duration,
+}
+
+impl Merge {
+    pub(crate) fn new(query: &MediathekQuery) -> Self {
+        Self {
+            query: query.clone(),
+            branches: Branch::all(query, page_size(query)),
+            ids: HashSet::new(),
+            skip: query.offset.unwrap_or(0),
+            page: Vec::new(),
+            filmliste_timestamp: 0,
+            search_engine_time: Duration::ZERO,
+        }
+    }
+
+    /// Return the index of the branch whose next results must be retrieved
+    /// before the next result can be merged, if any.
+    fn pending(&self) -> Option<usize> {
+        if self.query.sort_by.is_some() {
+            // the next result is the first one of any branch
+            self.branches
+                .iter()
+                .position(|branch| branch.results.is_empty() && !branch.exhausted)
+        } else {
+            // the branches are concatenated
+            self.branches
+                .iter()
+                .position(|branch| !branch.results.is_empty() || !branch.exhausted)
+                .filter(|&index| self.branches[index].results.is_empty())
+        }
+    }
+
+    /// Return the next query that should be sent to the server or `None` if
+    /// no further results are needed.
+    pub(crate) fn next_query(&self) -> Option<&MediathekQuery> {
+        self.pending().map(|index| &self.branches[index].query)
+    }
+
+    /// Process the response to the query returned by
+    /// [`next_query`](Self::next_query).
+    pub(crate) fn process(&mut self, result: QueryResult) {
+        let Some(index) = self.pending() else {
+            return;
+        };
+        self.filmliste_timestamp = self
+            .filmliste_timestamp
+            .max(result.query_info.filmliste_timestamp);
+        self.search_engine_time += result.query_info.search_engine_time;
+        self.branches[index].process(&self.query, result);
+    }
+
+    /// Remove the next result from the branches.
+    fn pop(&mut self) -> Option<Item> {
+        let branch = if self.query.sort_by.is_some() {
+            self.branches
+                .iter_mut()
+                .filter(|branch| !branch.results.is_empty())
+                .min_by(|a, b| compare(&self.query, &a.results[0], &b.results[0]))?
+        } else {
+            self.branches
+                .iter_mut()
+                .find(|branch| !branch.results.is_empty())?
+        };
+        branch.results.pop_front()
+    }
+
+    /// Merge the retrieved results into the next page.
+    ///
+    /// Returns `None` if further results must be retrieved first or if all
+    /// results have been merged. The total count of results is exact once all
+    /// results of all queries have been retrieved. Otherwise, it is the sum of
+    /// the totals reported by the server, which is an upper bound.
+    pub(crate) fn next_page(&mut self) -> Option<QueryResult> {
+        while self.page.len() < page_size(&self.query) {
+            if self.pending().is_some() {
+                return None;
+            }
+            let Some(item) = self.pop() else {
+                break;
+            };
+            if !self.ids.insert(item.id.clone()) {
+                continue;
+            }
+            if self.skip > 0 {
+                self.skip -= 1;
+            } else {
+                self.page.push(item);
+            }
+        }
+        if self.page.is_empty() {
+            return None;
+        }
+
+        let total_results = if self.branches.iter().all(|branch| branch.exhausted) {
+            let mut ids = self.ids.iter().collect::<HashSet<_>>();
+            let buffered = self
+                .branches
+                .iter()
+                .flat_map(|branch| &branch.results)
+                .filter(|item| ids.insert(&item.id))
+                .count();
+            (self.ids.len() + buffered) as u64
+        } else {
+            self.branches
+                .iter()
+                .map(|branch| branch.total_results)
+                .sum()
+        };
+        let results = mem::take(&mut self.page);
+        Some(QueryResult {
+            query_info: QueryInfo {
+                filmliste_timestamp: self.filmliste_timestamp,
+                result_count: results.len(),
+                search_engine_time: mem::take(&mut self.search_engine_time),
+                total_results,
+            },
+            results,
+        })
+    }
+}
+
+/// Compare two results like the server would sort them for a query with a
+/// sort field.
+fn compare(query: &MediathekQuery, a: &Item, b: &Item) -> Ordering {
+    let Some(sort_by) = query.sort_by else {
+        return Ordering::Equal;
+    };
+    let ordering = local::compare(sort_by, a, b);
+    match query.sort_order.unwrap_or(SortOrder::Descending) {
+        SortOrder::Ascending => ordering,
+        SortOrder::Descending => ordering.reverse(),
+    }
+}
+
+/// The count of results that each query must return to fill the requested
+/// page.
+fn wanted(query: &MediathekQuery) -> usize {
+    query.offset.unwrap_or(0) + page_size(query)
+}
+
+/// The requested count of results per page.
+fn page_size(query: &MediathekQuery) -> usize {
+    query.size.unwrap_or(DEFAULT_SIZE)
+}
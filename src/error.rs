@@ -1,44 +1,155 @@
 use std::fmt::Display;
 
-use crate::models::ApiError;
+use crate::{
+    filmliste::FormatError,
+    hls::PlaylistError,
+    models::{ApiError, Quality},
+    search_string::ParseError,
+    transport::TransportError,
+};
 
 /// Alias for the `Result`s returned by this library.
 pub type Result<T> = core::result::Result<T, Error>;
 
 /// An error returned by this client.
+///
+/// Some variants only exist if the corresponding feature is enabled.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
+    #[cfg(feature = "reqwest")]
     Reqwest(reqwest::Error),
+    Transport(TransportError),
+    Status(u16),
+    InvalidBaseUrl(String),
+    Json(serde_json::Error),
     EmptyResponse,
     Response(ApiError),
+    Parse(ParseError),
+    Io(std::io::Error),
+    Filmliste(FormatError),
+    Playlist(PlaylistError),
+    #[cfg(feature = "subtitles")]
+    Subtitles(crate::subtitles::SubtitleError),
+    #[cfg(feature = "feed")]
+    Feed(crate::feed::FeedError),
+    VariantUnavailable,
+    EncryptedStream,
+    QualityUnavailable(Quality),
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
 }
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            #[cfg(feature = "reqwest")]
             Error::Reqwest(_) => f.write_str("HTTP request failed"),
+            Error::Transport(_) => f.write_str("HTTP request failed"),
+            Error::Status(status) => write!(
+                f,
+                "mediathekviewweb server returned an unsuccessful status code {status}"
+            ),
+            Error::InvalidBaseUrl(url) => write!(f, "invalid base URL `{url}`"),
+            Error::Json(_) => f.write_str("failed to parse mediathekviewweb server response"),
             Error::EmptyResponse => {
                 f.write_str("mediathekviewweb server returned an empty response")
             }
             Error::Response(_) => f.write_str("mediathekviewweb server returned an error"),
+            Error::Parse(_) => f.write_str("failed to parse search string"),
+            Error::Io(_) => f.write_str("I/O operation failed"),
+            Error::Filmliste(_) => f.write_str("failed to parse Filmliste"),
+            Error::Playlist(_) => f.write_str("failed to parse HLS playlist"),
+            #[cfg(feature = "subtitles")]
+            Error::Subtitles(_) => f.write_str("failed to parse subtitles"),
+            #[cfg(feature = "feed")]
+            Error::Feed(_) => f.write_str("failed to parse RSS feed"),
+            Error::VariantUnavailable => {
+                f.write_str("HLS playlist contains no variant that matches the selection")
+            }
+            Error::EncryptedStream => f.write_str("encrypted HLS streams are not supported"),
+            Error::QualityUnavailable(quality) => {
+                write!(f, "video is not available in {quality:?} quality")
+            }
+            Error::SizeMismatch { expected, actual } => write!(
+                f,
+                "downloaded file has a size of {actual} bytes instead of {expected} bytes"
+            ),
         }
     }
 }
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
+            #[cfg(feature = "reqwest")]
             Error::Reqwest(e) => Some(e),
+            Error::Transport(e) => Some(e.as_ref()),
+            Error::Status(_) => None,
+            Error::InvalidBaseUrl(_) => None,
+            Error::Json(e) => Some(e),
             Error::EmptyResponse => None,
             Error::Response(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Filmliste(e) => Some(e),
+            Error::Playlist(e) => Some(e),
+            #[cfg(feature = "subtitles")]
+            Error::Subtitles(e) => Some(e),
+            #[cfg(feature = "feed")]
+            Error::Feed(e) => Some(e),
+            Error::VariantUnavailable => None,
+            Error::EncryptedStream => None,
+            Error::QualityUnavailable(_) => None,
+            Error::SizeMismatch { .. } => None,
         }
     }
 }
+#[cfg(feature = "reqwest")]
 impl From<reqwest::Error> for Error {
     fn from(e: reqwest::Error) -> Self {
         Error::Reqwest(e)
     }
 }
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
 impl From<ApiError> for Error {
     fn from(e: ApiError) -> Self {
         Error::Response(e)
     }
 }
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+impl From<FormatError> for Error {
+    fn from(e: FormatError) -> Self {
+        Error::Filmliste(e)
+    }
+}
+impl From<PlaylistError> for Error {
+    fn from(e: PlaylistError) -> Self {
+        Error::Playlist(e)
+    }
+}
+#[cfg(feature = "subtitles")]
+impl From<crate::subtitles::SubtitleError> for Error {
+    fn from(e: crate::subtitles::SubtitleError) -> Self {
+        Error::Subtitles(e)
+    }
+}
+#[cfg(feature = "feed")]
+impl From<crate::feed::FeedError> for Error {
+    fn from(e: crate::feed::FeedError) -> Self {
+        Error::Feed(e)
+    }
+}
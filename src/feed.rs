@@ -0,0 +1,927 @@
+//! Generation of RSS 2.0 and Atom feeds from query results, like the feed
+//! view of the MediathekViewWeb web interface.
+//!
+//! Every item becomes an entry that links to its website and includes its
+//! video as an enclosure, so the feeds can be subscribed to in feed readers
+//! and podcast clients. The enclosure's `length` is taken from
+//! [`Item::size`], which MediathekView reports for the normal quality, or is
+//! `0` if it is unknown. Dates are given in the Europe/Berlin time zone.
+//!
+//! In the other direction, [`Mediathek::feed`] requests the RSS feed of a
+//! MediathekViewWeb server, e.g. as a fallback if the JSON API is rate
+//! limited. Its entries are parsed into [`FeedItem`]s, which can be converted
+//! into [`Item`]s.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example(mediathek: mediathekviewweb::Mediathek) -> mediathekviewweb::Result<()> {
+//! use mediathekviewweb::{
+//!     feed::{Feed, FeedFormat},
+//!     models::{MediathekQuery, SortField},
+//! };
+//!
+//! let query = MediathekQuery {
+//!     sort_by: Some(SortField::Timestamp),
+//!     ..MediathekQuery::from_search_string("#sendung,mit,der,maus", false)?
+//! };
+//! let feed = Feed::new("Die Sendung mit der Maus", "https://example.org/maus")
+//!     .format(FeedFormat::Atom)
+//!     .generate(&mediathek, &query)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    borrow::Borrow,
+    fmt::Display,
+    time::{Duration, SystemTime},
+};
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader, XmlVersion,
+};
+
+use crate::{
+    date,
+    datetime::BerlinTime,
+    models::{Channel, Fallback, Item, MediathekQuery, Quality},
+    transport::{Method, Request},
+    Mediathek,
+};
+
+/// The format of a generated feed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FeedFormat {
+    /// RSS 2.0 with iTunes podcast extensions.
+    #[default]
+    Rss,
+    /// The Atom Syndication Format.
+    Atom,
+}
+impl FeedFormat {
+    /// Return the MIME type of feeds in this format, e.g. for the
+    /// `Content-Type` header.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+        }
+    }
+}
+
+/// The metadata and options of a generated feed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Feed {
+    pub title: String,
+    /// The URL of the feed's website. It is also used as the ID of Atom
+    /// feeds.
+    pub link: String,
+    /// A description of the feed. Defaults to the title.
+    pub description: Option<String>,
+    pub format: FeedFormat,
+    /// The preferred quality of the enclosed videos.
+    pub quality: Quality,
+    /// Which quality is chosen if a video isn't available in the preferred
+    /// quality.
+    pub fallback: Fallback,
+}
+impl Feed {
+    /// Create an RSS feed that encloses the highest available quality of
+    /// every video.
+    pub fn new(title: impl Into<String>, link: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            link: link.into(),
+            description: None,
+            format: FeedFormat::default(),
+            quality: Quality::Hd,
+            fallback: Fallback::Lower,
+        }
+    }
+    /// Set the description of the feed.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+    /// Set the format of the feed.
+    pub fn format(mut self, format: FeedFormat) -> Self {
+        self.format = format;
+        self
+    }
+    /// Set the preferred quality of the enclosed videos.
+    pub fn quality(mut self, quality: Quality) -> Self {
+        self.quality = quality;
+        self
+    }
+    /// Set the quality that is chosen if a video isn't available in the
+    /// preferred quality.
+    pub fn fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Send a query and return a feed of its results.
+    pub async fn generate(
+        &self,
+        mediathek: &Mediathek,
+        query: &MediathekQuery,
+    ) -> crate::Result<String> {
+        let result = mediathek.send_query(query).await?;
+        Ok(self.render(&result.results))
+    }
+
+    /// Return a feed of items or references to items.
+    ///
+    /// The feed's update date is the creation date of the newest Filmliste
+    /// that contained one of the items, or the current time if there are no
+    /// items.
+    pub fn render<I>(&self, items: I) -> String
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Item>,
+    {
+        let items = items.into_iter().collect::<Vec<_>>();
+        let updated = items
+            .iter()
+            .map(|item| item.borrow().filmliste_timestamp)
+            .max()
+            .unwrap_or_else(|| date::unix_timestamp(SystemTime::now()));
+        let items = items.iter().map(Borrow::borrow);
+        match self.format {
+            FeedFormat::Rss => self.render_rss(items, updated),
+            FeedFormat::Atom => self.render_atom(items, updated),
+        }
+    }
+
+    fn render_rss<'a>(&self, items: impl Iterator<Item = &'a Item>, updated: i64) -> String {
+        let mut feed = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\">\n\
+             <channel>\n",
+        );
+        let description = self.description.as_deref().unwrap_or(&self.title);
+        feed += &format!("<title>{}</title>\n", escape(&self.title));
+        feed += &format!("<link>{}</link>\n", escape(&self.link));
+        feed += &format!("<description>{}</description>\n", escape(description));
+        feed += &format!("<lastBuildDate>{}</lastBuildDate>\n", rfc2822(updated));
+        for item in items {
+            feed += "<item>\n";
+            feed += &format!("<title>{}</title>\n", escape(&item.label()));
+            feed += &format!("<link>{}</link>\n", escape(&item.url_website));
+            if let Some(description) = &item.description {
+                feed += &format!("<description>{}</description>\n", escape(description));
+            }
+            feed += &format!("<category>{}</category>\n", escape(item.channel.as_str()));
+            feed += &format!("<guid isPermaLink=\"false\">{}</guid>\n", escape(&item.id));
+            feed += &format!("<pubDate>{}</pubDate>\n", rfc2822(item.timestamp));
+            if let Some((_, url)) = item.select_url(self.quality, self.fallback) {
+                feed += &format!(
+                    "<enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\n",
+                    escape(url),
+                    item.size.unwrap_or(0),
+                    media_type(url)
+                );
+            }
+            if let Some(duration) = item.duration {
+                feed += &format!(
+                    "<itunes:duration>{}</itunes:duration>\n",
+                    duration.as_secs()
+                );
+            }
+            feed += "</item>\n";
+        }
+        feed += "</channel>\n</rss>\n";
+        feed
+    }
+
+    fn render_atom<'a>(&self, items: impl Iterator<Item = &'a Item>, updated: i64) -> String {
+        let mut feed = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+        );
+        feed += &format!("<title>{}</title>\n", escape(&self.title));
+        if let Some(description) = &self.description {
+            feed += &format!("<subtitle>{}</subtitle>\n", escape(description));
+        }
+        feed += &format!("<link href=\"{}\"/>\n", escape(&self.link));
+        feed += &format!("<id>{}</id>\n", escape(&self.link));
+        feed += &format!("<updated>{}</updated>\n", rfc3339(updated));
+        for item in items {
+            feed += "<entry>\n";
+            feed += &format!("<title>{}</title>\n", escape(&item.label()));
+            feed += &format!(
+                "<link rel=\"alternate\" href=\"{}\"/>\n",
+                escape(&item.url_website)
+            );
+            if let Some((_, url)) = item.select_url(self.quality, self.fallback) {
+                feed += &format!(
+                    "<link rel=\"enclosure\" href=\"{}\" type=\"{}\"",
+                    escape(url),
+                    media_type(url)
+                );
+                if let Some(size) = item.size {
+                    feed += &format!(" length=\"{size}\"");
+                }
+                feed += "/>\n";
+            }
+            feed += &format!("<id>urn:mediathekviewweb:{}</id>\n", escape(&item.id));
+            feed += &format!("<published>{}</published>\n", rfc3339(item.timestamp));
+            feed += &format!("<updated>{}</updated>\n", rfc3339(item.timestamp));
+            feed += &format!(
+                "<author><name>{}</name></author>\n",
+                escape(item.channel.display_name())
+            );
+            if let Some(description) = &item.description {
+                feed += &format!("<summary>{}</summary>\n", escape(description));
+            }
+            feed += "</entry>\n";
+        }
+        feed += "</feed>\n";
+        feed
+    }
+}
+
+/// Escapes the characters that have a special meaning in XML.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // XML parsers normalize raw `\r\n` to `\n`
+            '\r' => escaped.push_str("&#13;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Guesses the media type of a video from the extension of its URL.
+fn media_type(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let extension = path.rsplit_once('.').map_or("", |(_, extension)| extension);
+    match extension.to_ascii_lowercase().as_str() {
+        "m3u8" => "application/vnd.apple.mpegurl",
+        "webm" => "video/webm",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        _ => "video/mp4",
+    }
+}
+
+/// Formats a unix timestamp as specified by RFC 2822, as required by RSS.
+fn rfc2822(timestamp: i64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let local = BerlinTime::saturating(timestamp);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} +{:02}00",
+        WEEKDAYS[usize::from(local.weekday()) - 1],
+        local.day,
+        MONTHS[usize::from(local.month) - 1],
+        local.year,
+        local.hour,
+        local.minute,
+        local.second,
+        local.offset / 3600
+    )
+}
+
+/// Formats a unix timestamp as specified by RFC 3339, as required by Atom.
+fn rfc3339(timestamp: i64) -> String {
+    BerlinTime::saturating(timestamp).to_string()
+}
+
+/// An error caused by a malformed RSS feed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeedError {
+    /// The byte offset in the document where the error occured.
+    pub offset: u64,
+    /// A description of the error.
+    pub message: String,
+}
+impl Display for FeedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+impl std::error::Error for FeedError {}
+
+/// An entry of an RSS feed, e.g. the one returned by [`Mediathek::feed`].
+///
+/// Feeds contain less information than the JSON API, e.g. there is only a
+/// single video URL. Use [`Item::from`] to convert an entry to an [`Item`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeedItem {
+    pub channel: Channel,
+    pub topic: String,
+    pub title: String,
+    pub description: Option<String>,
+    /// The broadcasting date as a unix timestamp, or `0` if the entry has no
+    /// valid date.
+    pub timestamp: i64,
+    /// The duration, or `None` if the entry has no valid duration.
+    pub duration: Option<Duration>,
+    pub size: Option<usize>,
+    pub url_video: String,
+    pub url_website: String,
+    /// The entry's `guid`, or the video URL if it has none.
+    pub id: String,
+}
+/// The fields that feeds don't contain are left empty. The
+/// [`filmliste_timestamp`](Item::filmliste_timestamp) is `0`.
+impl From<FeedItem> for Item {
+    fn from(item: FeedItem) -> Self {
+        Item {
+            channel: item.channel,
+            topic: item.topic,
+            title: item.title,
+            description: item.description,
+            timestamp: item.timestamp,
+            duration: item.duration,
+            size: item.size,
+            url_website: item.url_website,
+            url_subtitle: None,
+            url_video: item.url_video,
+            url_video_low: None,
+            url_video_hd: None,
+            filmliste_timestamp: 0,
+            id: item.id,
+        }
+    }
+}
+
+/// Parse the entries of an RSS feed.
+///
+/// Besides the standard RSS elements, the `duration` and `websiteUrl`
+/// elements of MediathekViewWeb's feed and `itunes:duration` are supported.
+/// The channel and topic are taken from the first two `category` elements
+/// and removed from the beginning of the title. Feeds generated by
+/// [`Feed`] can be parsed as well.
+///
+/// Malformed dates and durations of entries are ignored. Only feeds that
+/// aren't well-formed XML return an error.
+pub fn parse_rss(xml: &str) -> Result<Vec<FeedItem>, FeedError> {
+    RssParser {
+        reader: Reader::from_str(xml),
+        text: String::new(),
+        item: None,
+        items: Vec::new(),
+    }
+    .parse()
+}
+
+impl Mediathek {
+    /// Request the RSS feed of a search string using the advanced search
+    /// syntax from the server's `/feed` endpoint and parse its entries.
+    ///
+    /// This can be used as an alternative to the JSON API. The results are
+    /// sorted by broadcasting date and limited by the server. If `everywhere`
+    /// is `true`, the description is searched as well.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example(mediathek: mediathekviewweb::Mediathek) -> mediathekviewweb::Result<()> {
+    /// use mediathekviewweb::models::Item;
+    ///
+    /// let items = mediathek
+    ///     .feed("!ard #tagesschau", false)
+    ///     .await?
+    ///     .into_iter()
+    ///     .map(Item::from)
+    ///     .collect::<Vec<_>>();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn feed(
+        &self,
+        search_string: &str,
+        everywhere: bool,
+    ) -> crate::Result<Vec<FeedItem>> {
+        let mut path = format!("/feed?query={}", percent_encode(search_string));
+        if everywhere {
+            path += "&everywhere=true";
+        }
+        let response = self
+            .send_request(Request {
+                method: Method::Get,
+                path,
+                headers: Vec::new(),
+                body: Vec::new(),
+            })
+            .await?;
+        if !response.is_success() {
+            return Err(crate::Error::Status(response.status));
+        }
+        let xml = String::from_utf8_lossy(&response.body);
+        Ok(parse_rss(&xml)?)
+    }
+}
+
+/// The raw fields of an RSS item.
+#[derive(Debug, Default)]
+struct RawItem {
+    title: String,
+    description: Option<String>,
+    link: Option<String>,
+    categories: Vec<String>,
+    guid: Option<String>,
+    timestamp: Option<i64>,
+    duration: Option<Duration>,
+    website: Option<String>,
+    enclosure: Option<(String, Option<usize>)>,
+}
+
+struct RssParser<'a> {
+    reader: Reader<&'a [u8]>,
+    /// The text of the element that is currently parsed.
+    text: String,
+    /// The item that is currently parsed.
+    item: Option<RawItem>,
+    items: Vec<FeedItem>,
+}
+impl RssParser<'_> {
+    fn parse(mut self) -> Result<Vec<FeedItem>, FeedError> {
+        loop {
+            let event = self.reader.read_event().map_err(|e| FeedError {
+                offset: self.reader.error_position(),
+                message: e.to_string(),
+            })?;
+            match event {
+                Event::Start(element) => {
+                    self.text.clear();
+                    self.start(&element)?;
+                }
+                Event::Empty(element) => {
+                    self.text.clear();
+                    self.start(&element)?;
+                    self.end(element.name().as_ref());
+                }
+                Event::End(element) => self.end(element.name().as_ref()),
+                Event::Text(text) => self.text += &text.xml10_content(),
+                Event::CData(text) => self.text += &text.xml10_content(),
+                Event::GeneralRef(reference) => match reference.resolve_char_ref() {
+                    Ok(Some(c)) => self.text.push(c),
+                    Ok(None) => {
+                        self.text += quick_xml::escape::resolve_predefined_entity(&reference)
+                            .ok_or_else(|| {
+                                self.error(format!("unknown entity `{}`", &*reference))
+                            })?
+                    }
+                    Err(e) => return Err(self.error(e.to_string())),
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(self.items)
+    }
+
+    fn start(&mut self, element: &BytesStart) -> Result<(), FeedError> {
+        match element.name().as_ref() {
+            "item" => self.item = Some(RawItem::default()),
+            "enclosure" => {
+                let mut url = None;
+                let mut length = None;
+                for attribute in element.attributes() {
+                    let attribute = attribute.map_err(|e| self.error(e.to_string()))?;
+                    let value = attribute
+                        .normalized_value(XmlVersion::Implicit1_0)
+                        .map_err(|e| self.error(e.to_string()))?;
+                    match attribute.key.as_ref() {
+                        "url" => url = Some(value.into_owned()),
+                        // `0` is commonly used if the size is unknown
+                        "length" => length = value.trim().parse().ok().filter(|&n| n > 0),
+                        _ => {}
+                    }
+                }
+                if let (Some(item), Some(url)) = (&mut self.item, url) {
+                    item.enclosure = Some((url, length));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Process the end of an element.
+    ///
+    /// Malformed dates and durations are ignored, so a single broken entry
+    /// doesn't fail the entire feed.
+    fn end(&mut self, name: &str) {
+        let text = std::mem::take(&mut self.text).trim().to_owned();
+        if name == "item" {
+            if let Some(item) = self.item.take() {
+                self.items.push(item.into_feed_item());
+            }
+            return;
+        }
+        let Some(item) = &mut self.item else {
+            return;
+        };
+        match name {
+            "title" => item.title = text,
+            "description" => item.description = Some(text).filter(|text| !text.is_empty()),
+            "link" => item.link = Some(text),
+            "category" => item.categories.push(text),
+            "guid" => item.guid = Some(text),
+            "websiteUrl" => item.website = Some(text),
+            "pubDate" => item.timestamp = parse_rfc2822(&text),
+            "duration" | "itunes:duration" => item.duration = parse_duration(&text),
+            _ => {}
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> FeedError {
+        FeedError {
+            offset: self.reader.buffer_position(),
+            message: message.into(),
+        }
+    }
+}
+
+impl RawItem {
+    fn into_feed_item(self) -> FeedItem {
+        let mut categories = self.categories.into_iter();
+        let channel = categories.next().unwrap_or_default();
+        let mut topic = categories.next().unwrap_or_default();
+
+        // strip the channel and topic that are repeated in the title, e.g.
+        // `[ARD] tagesschau - tagesschau 20:00 Uhr` or, in feeds generated by
+        // `Feed`, `ARD – tagesschau – tagesschau 20:00 Uhr`
+        let mut title = self.title.as_str();
+        if let Some(rest) = title.strip_prefix(&format!("[{channel}] ")) {
+            title = rest;
+        } else if let Some(rest) = title.strip_prefix(&format!("{channel} – ")) {
+            title = rest;
+            if topic.is_empty() {
+                if let Some((prefix, rest)) = title.split_once(" – ") {
+                    topic = prefix.to_owned();
+                    title = rest;
+                }
+            }
+        }
+        for separator in [" - ", " – "] {
+            if let Some(rest) = title.strip_prefix(&format!("{topic}{separator}")) {
+                title = rest;
+            }
+        }
+
+        let (url_video, size) = match (self.enclosure, &self.link) {
+            (Some((url, size)), _) => (url, size),
+            (None, link) => (link.clone().unwrap_or_default(), None),
+        };
+        let url_website = self.website.or(self.link).unwrap_or_default();
+        FeedItem {
+            channel: channel.into(),
+            topic,
+            title: title.to_owned(),
+            description: self.description,
+            timestamp: self.timestamp.unwrap_or(0),
+            duration: self.duration,
+            size,
+            id: self.guid.unwrap_or_else(|| url_video.clone()),
+            url_video,
+            url_website,
+        }
+    }
+}
+
+/// Parses a duration in seconds or in the form `[[HH:]MM:]SS`. Returns `None`
+/// for empty durations, e.g. of livestreams, and malformed ones.
+fn parse_duration(s: &str) -> Option<Duration> {
+    let mut seconds = 0u64;
+    for (i, part) in s.split(':').enumerate() {
+        if i > 2 || part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        seconds = seconds.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+    }
+    Some(Duration::from_secs(seconds))
+}
+
+/// Parses a date as specified by RFC 2822, e.g.
+/// `Mon, 02 Oct 2023 20:00:00 +0200`.
+fn parse_rfc2822(s: &str) -> Option<i64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+
+    // the day of the week is optional
+    let s = s.split_once(',').map_or(s, |(_, date)| date);
+    let mut parts = s.split_whitespace();
+    let day = parts.next()?.parse().ok()?;
+    let month = parts.next()?.to_ascii_lowercase();
+    let month = MONTHS.iter().position(|&m| m == month)? as u32 + 1;
+    let year = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':');
+    let hour = time.next()?.parse().ok()?;
+    let minute = time.next()?.parse().ok()?;
+    let second = time.next().map_or(Some(0), |s| s.parse().ok())?;
+    let offset = match parts.next()? {
+        "GMT" | "UT" | "UTC" | "Z" => 0,
+        zone => {
+            let (sign, digits) = zone.split_at_checked(1)?;
+            if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let minutes =
+                digits[..2].parse::<i64>().ok()? * 60 + digits[2..].parse::<i64>().ok()?;
+            match sign {
+                "+" => minutes * 60,
+                "-" => -minutes * 60,
+                _ => return None,
+            }
+        }
+    };
+    date::timestamp_from_civil(year, month, day, hour, minute, second)?.checked_sub(offset)
+}
+
+/// Percent-encodes a query parameter.
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded += &format!("%{byte:02X}"),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        parse_duration, parse_rfc2822, parse_rss, rfc2822, rfc3339, Feed, FeedError, FeedFormat,
+        FeedItem,
+    };
+    use crate::{
+        mock::fixtures,
+        models::{Channel, Fallback, Item, Quality},
+        transport::{BoxFuture, Method, Request, Response, Transport, TransportError},
+        Mediathek,
+    };
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(rfc2822(1696269600), "Mon, 02 Oct 2023 20:00:00 +0200");
+        assert_eq!(rfc2822(1700000000), "Tue, 14 Nov 2023 23:13:20 +0100");
+        assert_eq!(rfc3339(1696269600), "2023-10-02T20:00:00+02:00");
+        assert_eq!(rfc3339(0), "1970-01-01T01:00:00+01:00");
+    }
+
+    #[test]
+    fn test_parse_dates() {
+        assert_eq!(
+            parse_rfc2822("Mon, 02 Oct 2023 20:00:00 +0200"),
+            Some(1696269600)
+        );
+        assert_eq!(parse_rfc2822("2 Oct 2023 18:00 GMT"), Some(1696269600));
+        assert_eq!(parse_rfc2822(&rfc2822(1700000000)), Some(1700000000));
+        assert_eq!(parse_rfc2822("Mon, 02 Oct 2023 20:00:00"), None);
+        assert_eq!(parse_rfc2822("Mon, 32 Oct 2023 20:00:00 GMT"), None);
+        assert_eq!(parse_rfc2822("2023-10-02T20:00:00+02:00"), None);
+        assert_eq!(parse_rfc2822("31 Feb 2023 20:00:00 +0200"), None);
+        assert_eq!(
+            parse_rfc2822("29 Feb 2024 20:00:00 +0200"),
+            Some(1709229600)
+        );
+        assert_eq!(
+            parse_rfc2822("02 Oct 99999999999999999 20:00:00 +0200"),
+            None
+        );
+        assert_eq!(parse_rfc2822("02 Oct 2023 4294967296:00:00 +0200"), None);
+
+        assert_eq!(parse_duration("932"), Some(Duration::from_secs(932)));
+        assert_eq!(parse_duration("1:02:05"), Some(Duration::from_secs(3725)));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("15 min"), None);
+        assert_eq!(parse_duration("400000000000000000:0"), None);
+        assert_eq!(parse_duration("99999999999999999999"), None);
+    }
+
+    #[test]
+    fn test_rss() {
+        let items = fixtures();
+        let feed = Feed::new("tagesschau & mehr", "https://example.org/feed")
+            .quality(Quality::Normal)
+            .render(&items[..6]);
+
+        assert!(feed.starts_with("<?xml"));
+        assert!(feed.contains("<title>tagesschau &amp; mehr</title>"));
+        assert!(feed.contains("<description>tagesschau &amp; mehr</description>"));
+        assert!(feed.contains("<lastBuildDate>Tue, 03 Oct 2023 15:17:00 +0200</lastBuildDate>"));
+        assert_eq!(feed.matches("<item>").count(), 6);
+        assert!(feed.contains("<title>ARD – tagesschau – tagesschau 20:00 Uhr</title>"));
+        assert!(feed.contains("<pubDate>Mon, 02 Oct 2023 20:00:00 +0200</pubDate>"));
+        assert!(feed.contains(&format!(
+            "<enclosure url=\"{}\" length=\"137363456\" type=\"video/mp4\"/>",
+            items[0].url_video
+        )));
+        assert!(feed.contains("<itunes:duration>932</itunes:duration>"));
+        // the livestream has no size
+        assert!(feed.contains(&format!(
+            "<enclosure url=\"{}\" length=\"0\" type=\"application/vnd.apple.mpegurl\"/>",
+            items[5].url_video
+        )));
+        assert!(feed.ends_with("</channel>\n</rss>\n"));
+    }
+
+    #[test]
+    fn test_atom() {
+        let items = fixtures();
+        let feed = Feed::new("Dokumentationen", "https://example.org/<feed>")
+            .description("ARTE")
+            .format(FeedFormat::Atom)
+            .quality(Quality::Low)
+            .fallback(Fallback::None)
+            .render(&items[8..]);
+
+        assert!(feed.contains("<id>https://example.org/&lt;feed&gt;</id>"));
+        assert!(feed.contains("<subtitle>ARTE</subtitle>"));
+        assert!(feed.contains("<updated>2023-10-03T15:17:00+02:00</updated>"));
+        assert_eq!(feed.matches("<entry>").count(), 2);
+        assert!(feed.contains(&format!(
+            "<link rel=\"enclosure\" href=\"{}\" type=\"video/mp4\" length=\"537919488\"/>",
+            items[8].url_video_low.as_deref().unwrap()
+        )));
+        // the ARTE video is not available in low quality
+        assert_eq!(feed.matches("rel=\"enclosure\"").count(), 1);
+        assert!(feed.contains("<author><name>ARTE (Deutsch)</name></author>"));
+        assert!(feed.contains(&format!("<id>urn:mediathekviewweb:{}</id>", items[9].id)));
+        assert!(feed.ends_with("</feed>\n"));
+
+        assert_eq!(
+            FeedFormat::Atom.content_type(),
+            "application/atom+xml; charset=utf-8"
+        );
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_generate() -> Result<(), Box<dyn std::error::Error>> {
+        let server = crate::mock::MockServer::start()?;
+        let mediathek = Mediathek::new_with_url(server.url(), "test".parse().unwrap())?;
+        let query = crate::models::MediathekQuery::from_search_string("#livestream", false)?;
+        let feed = Feed::new("Livestreams", "https://example.org")
+            .generate(&mediathek, &query)
+            .await?;
+        assert_eq!(feed.matches("<item>").count(), 2);
+        Ok(())
+    }
+
+    const MEDIATHEKVIEWWEB_FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+<channel>
+<title><![CDATA[MediathekViewWeb]]></title>
+<link>https://mediathekviewweb.de</link>
+<item>
+<title><![CDATA[tagesschau - tagesschau 20:00 Uhr]]></title>
+<description><![CDATA[Themen der Sendung: Krieg & Frieden]]></description>
+<link>https://media.tagesschau.de/video/tagesschau.mp4</link>
+<guid isPermaLink="false">tCqbLt9pqWaDkhZ7Yz1cgWwiMaGQiSnUsz5ysqr/b38=</guid>
+<category><![CDATA[ARD]]></category>
+<category><![CDATA[tagesschau]]></category>
+<pubDate>Mon, 02 Oct 2023 18:00:00 GMT</pubDate>
+<enclosure url="https://media.tagesschau.de/video/tagesschau.mp4" length="137363456" type="video/mp4"/>
+<duration>932</duration>
+<websiteUrl>https://www.tagesschau.de/multimedia/sendung/tagesschau_20_uhr</websiteUrl>
+</item>
+<item>
+<title>Livestream - Das Erste &amp; mehr</title>
+<link>https://daserste-live.ard-mcdn.de/daserste/live/hls/de/master.m3u8</link>
+<category>ARD</category>
+<category>Livestream</category>
+<pubDate>Tue, 03 Oct 2023 15:17:00 +0200</pubDate>
+<duration></duration>
+</item>
+</channel>
+</rss>
+"#;
+
+    #[test]
+    fn test_parse_rss() -> Result<(), FeedError> {
+        let items = parse_rss(MEDIATHEKVIEWWEB_FEED)?;
+        assert_eq!(
+            items,
+            [
+                FeedItem {
+                    channel: Channel::Ard,
+                    topic: "tagesschau".into(),
+                    title: "tagesschau 20:00 Uhr".into(),
+                    description: Some("Themen der Sendung: Krieg & Frieden".into()),
+                    timestamp: 1696269600,
+                    duration: Some(Duration::from_secs(932)),
+                    size: Some(137363456),
+                    url_video: "https://media.tagesschau.de/video/tagesschau.mp4".into(),
+                    url_website: "https://www.tagesschau.de/multimedia/sendung/tagesschau_20_uhr"
+                        .into(),
+                    id: "tCqbLt9pqWaDkhZ7Yz1cgWwiMaGQiSnUsz5ysqr/b38=".into(),
+                },
+                FeedItem {
+                    channel: Channel::Ard,
+                    topic: "Livestream".into(),
+                    title: "Das Erste & mehr".into(),
+                    description: None,
+                    timestamp: 1696339020,
+                    duration: None,
+                    size: None,
+                    url_video: "https://daserste-live.ard-mcdn.de/daserste/live/hls/de/master.m3u8"
+                        .into(),
+                    url_website:
+                        "https://daserste-live.ard-mcdn.de/daserste/live/hls/de/master.m3u8".into(),
+                    id: "https://daserste-live.ard-mcdn.de/daserste/live/hls/de/master.m3u8".into(),
+                },
+            ]
+        );
+
+        // malformed dates and durations don't fail the entire feed
+        let items = parse_rss(
+            "<rss>\
+             <item><title>A</title><pubDate>gestern</pubDate><duration>15 min</duration></item>\
+             <item><title>B</title><duration>400000000000000000:0</duration></item>\
+             <item><title>C</title><pubDate>02 Oct 2023 18:00 GMT</pubDate></item>\
+             </rss>",
+        )?;
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| (item.title.as_str(), item.timestamp, item.duration))
+                .collect::<Vec<_>>(),
+            [("A", 0, None), ("B", 0, None), ("C", 1696269600, None)]
+        );
+        assert!(parse_rss("<rss><item><title>&unknown;</title></item></rss>").is_err());
+        Ok(())
+    }
+
+    /// Feeds generated by [`Feed`] can be parsed into the original items.
+    #[test]
+    fn test_round_trip() -> Result<(), FeedError> {
+        let items = fixtures();
+        let feed = Feed::new("Alle", "https://example.org")
+            .quality(Quality::Normal)
+            .render(&items);
+        let parsed = parse_rss(&feed)?;
+        assert_eq!(parsed.len(), items.len());
+        for (parsed, item) in parsed.into_iter().zip(items) {
+            assert_eq!(
+                Item::from(parsed),
+                Item {
+                    url_subtitle: None,
+                    url_video_low: None,
+                    url_video_hd: None,
+                    filmliste_timestamp: 0,
+                    ..item
+                }
+            );
+        }
+        Ok(())
+    }
+
+    /// Answers requests for the feed of `!ard livestream`.
+    #[derive(Debug)]
+    struct FeedTransport;
+    impl Transport for FeedTransport {
+        fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, TransportError>> {
+            let found = request.method == Method::Get
+                && request.path == "/feed?query=%21ard%20livestream&everywhere=true";
+            Box::pin(async move {
+                Ok(Response {
+                    status: if found { 200 } else { 404 },
+                    headers: Vec::new(),
+                    body: if found {
+                        MEDIATHEKVIEWWEB_FEED.into()
+                    } else {
+                        Vec::new()
+                    },
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_feed() -> crate::Result<()> {
+        let mediathek = Mediathek::with_transport(FeedTransport);
+        let items = mediathek.feed("!ard livestream", true).await?;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].title, "Das Erste & mehr");
+
+        assert!(matches!(
+            mediathek.feed("!ard livestream", false).await,
+            Err(crate::Error::Status(404))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,534 @@
+//! Streaming parser for the film list ("Filmliste") published by
+//! [MediathekView](https://mediathekview.de).
+//!
+//! The Filmliste is a large JSON object that contains one `"X"` array per
+//! item. To save space, the channel and topic are omitted if they are the same
+//! as in the previous item and the URLs of the low and HD quality videos are
+//! stored relative to the normal quality URL. [`FilmlisteReader`] reads the
+//! items one by one and converts them into [`Item`]s without loading the whole
+//! file into memory.
+//!
+//! # Example
+//! ```rust,no_run
+//! # fn main() -> mediathekviewweb::Result<()> {
+//! use mediathekviewweb::filmliste::FilmlisteReader;
+//!
+//! let file = std::io::BufReader::new(std::fs::File::open("filmliste.json")?);
+//! for item in FilmlisteReader::new(file)? {
+//!     let item = item?;
+//!     println!("{} - {} - {}", item.channel, item.topic, item.title);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt::Display,
+    io::{BufRead, ErrorKind},
+    time::Duration,
+};
+
+use crate::models::Item;
+
+/// Metadata of a Filmliste.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilmlisteHeader {
+    /// The time when the Filmliste was created as a unix timestamp.
+    pub timestamp: i64,
+    /// The version of the Filmliste format.
+    pub version: String,
+    /// The program that created the Filmliste.
+    pub program: String,
+    /// The ID of the Filmliste.
+    pub id: String,
+}
+
+/// An error caused by a malformed Filmliste.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormatError {
+    /// The byte offset in the (uncompressed) Filmliste where the error occured.
+    pub offset: u64,
+    /// A description of the error.
+    pub message: String,
+}
+impl Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+impl std::error::Error for FormatError {}
+
+const CHANNEL: usize = 0;
+const TOPIC: usize = 1;
+const TITLE: usize = 2;
+const DURATION: usize = 5;
+const SIZE: usize = 6;
+const DESCRIPTION: usize = 7;
+const URL_VIDEO: usize = 8;
+const URL_WEBSITE: usize = 9;
+const URL_SUBTITLE: usize = 10;
+const URL_VIDEO_LOW: usize = 12;
+const URL_VIDEO_HD: usize = 14;
+const TIMESTAMP: usize = 16;
+
+/// Reads [`Item`]s from an uncompressed Filmliste.
+#[derive(Debug)]
+pub struct FilmlisteReader<R> {
+    json: JsonReader<R>,
+    header: FilmlisteHeader,
+    channel: String,
+    topic: String,
+    finished: bool,
+}
+impl<R: BufRead> FilmlisteReader<R> {
+    /// Create a new reader and read the header of the Filmliste.
+    pub fn new(reader: R) -> crate::Result<Self> {
+        let mut json = JsonReader { reader, offset: 0 };
+
+        json.expect(b'{')?;
+        let (_, header) = json.read_entry()?;
+        if header.len() < 5 {
+            return Err(json.error("incomplete Filmliste header").into());
+        }
+        let timestamp = parse_header_date(&header[1])
+            .ok_or_else(|| json.error("invalid date in Filmliste header"))?;
+        let mut header = header.into_iter();
+        header.nth(1);
+        let header = FilmlisteHeader {
+            timestamp,
+            version: header.next().unwrap_or_default(),
+            program: header.next().unwrap_or_default(),
+            id: header.next().unwrap_or_default(),
+        };
+
+        // the second entry contains the column names
+        json.expect(b',')?;
+        json.read_entry()?;
+
+        Ok(Self {
+            json,
+            header,
+            channel: String::new(),
+            topic: String::new(),
+            finished: false,
+        })
+    }
+
+    /// The metadata of the Filmliste.
+    pub fn header(&self) -> &FilmlisteHeader {
+        &self.header
+    }
+
+    fn read_item(&mut self) -> crate::Result<Option<Item>> {
+        loop {
+            match self.json.next_non_whitespace()? {
+                b'}' => return Ok(None),
+                b',' => {}
+                _ => return Err(self.json.error("expected `,` or `}`").into()),
+            }
+
+            let (key, mut fields) = self.json.read_entry()?;
+            if key != "X" {
+                continue;
+            }
+            if fields.len() <= TIMESTAMP {
+                return Err(self.json.error("incomplete Filmliste entry").into());
+            }
+
+            if fields[CHANNEL].is_empty() {
+                fields[CHANNEL].clone_from(&self.channel);
+            } else {
+                self.channel.clone_from(&fields[CHANNEL]);
+            }
+            if fields[TOPIC].is_empty() {
+                fields[TOPIC].clone_from(&self.topic);
+            } else {
+                self.topic.clone_from(&fields[TOPIC]);
+            }
+
+            let duration = parse_duration(&fields[DURATION]);
+            let size = fields[SIZE]
+                .parse::<usize>()
+                .ok()
+                .and_then(|megabytes| megabytes.checked_mul(1024 * 1024));
+            let timestamp = fields[TIMESTAMP].parse().unwrap_or_default();
+            let url_video_low = expand_url(&fields[URL_VIDEO], &fields[URL_VIDEO_LOW]);
+            let url_video_hd = expand_url(&fields[URL_VIDEO], &fields[URL_VIDEO_HD]);
+            let id = item_id(&fields);
+
+            let mut take = |i: usize| std::mem::take(&mut fields[i]);
+
+            return Ok(Some(Item {
+                channel: take(CHANNEL).into(),
+                topic: take(TOPIC),
+                title: take(TITLE),
+                description: Some(take(DESCRIPTION)).filter(|s| !s.is_empty()),
+                timestamp,
+                duration,
+                size,
+                url_website: take(URL_WEBSITE),
+                url_subtitle: Some(take(URL_SUBTITLE)).filter(|s| !s.is_empty()),
+                url_video: take(URL_VIDEO),
+                url_video_low,
+                url_video_hd,
+                filmliste_timestamp: self.header.timestamp,
+                id,
+            }));
+        }
+    }
+}
+#[cfg(feature = "xz")]
+impl<R: std::io::Read> FilmlisteReader<std::io::BufReader<liblzma::read::XzDecoder<R>>> {
+    /// Create a new reader for a xz-compressed Filmliste and read its header.
+    pub fn new_xz(reader: R) -> crate::Result<Self> {
+        Self::new(std::io::BufReader::new(liblzma::read::XzDecoder::new(
+            reader,
+        )))
+    }
+}
+impl<R: BufRead> Iterator for FilmlisteReader<R> {
+    type Item = crate::Result<Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let item = self.read_item().transpose();
+        if !matches!(item, Some(Ok(_))) {
+            self.finished = true;
+        }
+        item
+    }
+}
+
+/// Parses a date like `23.10.2023, 17:48` in UTC.
+fn parse_header_date(s: &str) -> Option<i64> {
+    let (date, time) = s.split_once(", ")?;
+    let mut date = date.splitn(3, '.').map(str::parse::<u32>);
+    let (day, month, year) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let (hours, minutes) = time.split_once(':')?;
+    let (hours, minutes) = (hours.parse().ok()?, minutes.parse().ok()?);
+
+    crate::date::timestamp_from_civil(year.into(), month, day, hours, minutes, 0)
+}
+
+/// Parses a duration like `01:02:03`.
+fn parse_duration(s: &str) -> Option<Duration> {
+    let mut parts = s.splitn(3, ':').map(str::parse::<u64>);
+    let (hours, minutes, seconds) = (
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+    let seconds = hours
+        .checked_mul(3600)?
+        .checked_add(minutes.checked_mul(60)?)?
+        .checked_add(seconds)?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Reconstructs a URL stored as `offset|suffix` relative to `base`.
+fn expand_url(base: &str, url: &str) -> Option<String> {
+    if url.is_empty() {
+        return None;
+    }
+    match url.split_once('|') {
+        Some((offset, suffix)) => {
+            let prefix = base.get(..offset.parse().ok()?)?;
+            Some(format!("{prefix}{suffix}"))
+        }
+        None => Some(url.to_owned()),
+    }
+}
+
+/// Derives a stable ID from the fields that identify an item.
+///
+/// The IDs are not the same as the ones generated by MediathekViewWeb.
+fn item_id(fields: &[String]) -> String {
+    let hash = crate::fnv1a(
+        [CHANNEL, TOPIC, TITLE, URL_VIDEO]
+            .into_iter()
+            .flat_map(|field| fields[field].bytes().chain([0])),
+    );
+    format!("{hash:016x}")
+}
+
+/// A minimal pull parser for JSON objects whose values are arrays of strings.
+#[derive(Debug)]
+struct JsonReader<R> {
+    reader: R,
+    offset: u64,
+}
+impl<R: BufRead> JsonReader<R> {
+    fn error(&self, message: impl Into<String>) -> FormatError {
+        FormatError {
+            offset: self.offset,
+            message: message.into(),
+        }
+    }
+
+    fn next_byte(&mut self) -> crate::Result<u8> {
+        let byte = loop {
+            match self.reader.fill_buf() {
+                Ok([]) => return Err(self.error("unexpected end of file").into()),
+                Ok(buf) => break buf[0],
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        self.reader.consume(1);
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn next_non_whitespace(&mut self) -> crate::Result<u8> {
+        loop {
+            match self.next_byte()? {
+                b' ' | b'\t' | b'\n' | b'\r' => continue,
+                byte => return Ok(byte),
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> crate::Result<()> {
+        if self.next_non_whitespace()? == expected {
+            Ok(())
+        } else {
+            Err(self
+                .error(format!("expected `{}`", char::from(expected)))
+                .into())
+        }
+    }
+
+    /// Read a `"key": ["value", ...]` pair.
+    fn read_entry(&mut self) -> crate::Result<(String, Vec<String>)> {
+        self.expect(b'"')?;
+        let key = self.read_string()?;
+        self.expect(b':')?;
+        self.expect(b'[')?;
+
+        let mut values = Vec::new();
+        loop {
+            match self.next_non_whitespace()? {
+                b']' if values.is_empty() => break,
+                b'"' => values.push(self.read_string()?),
+                _ => return Err(self.error("expected string").into()),
+            }
+            match self.next_non_whitespace()? {
+                b',' => continue,
+                b']' => break,
+                _ => return Err(self.error("expected `,` or `]`").into()),
+            }
+        }
+
+        Ok((key, values))
+    }
+
+    /// Read the rest of a string after the opening quote.
+    fn read_string(&mut self) -> crate::Result<String> {
+        let mut bytes = Vec::new();
+        loop {
+            let (len, found) = match self.reader.fill_buf() {
+                Ok([]) => return Err(self.error("unterminated string").into()),
+                Ok(buf) => {
+                    let len = buf
+                        .iter()
+                        .position(|&b| b == b'"' || b == b'\\')
+                        .unwrap_or(buf.len());
+                    bytes.extend_from_slice(&buf[..len]);
+                    (len, len < buf.len())
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            self.reader.consume(len);
+            self.offset += len as u64;
+            if !found {
+                continue;
+            }
+
+            match self.next_byte()? {
+                b'"' => break,
+                _ => match self.next_byte()? {
+                    b'"' => bytes.push(b'"'),
+                    b'\\' => bytes.push(b'\\'),
+                    b'/' => bytes.push(b'/'),
+                    b'b' => bytes.push(0x08),
+                    b'f' => bytes.push(0x0c),
+                    b'n' => bytes.push(b'\n'),
+                    b'r' => bytes.push(b'\r'),
+                    b't' => bytes.push(b'\t'),
+                    b'u' => {
+                        let c = self.read_unicode_escape()?;
+                        bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    }
+                    _ => return Err(self.error("invalid escape sequence").into()),
+                },
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8").into())
+    }
+
+    /// Read the hex digits of a `\u` escape, including a following low
+    /// surrogate if necessary.
+    fn read_unicode_escape(&mut self) -> crate::Result<char> {
+        let high = self.read_hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if self.next_byte()? != b'\\' || self.next_byte()? != b'u' {
+                return Err(self.error("unpaired surrogate").into());
+            }
+            let low = self.read_hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("unpaired surrogate").into());
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape").into())
+    }
+
+    fn read_hex4(&mut self) -> crate::Result<u32> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = char::from(self.next_byte()?)
+                .to_digit(16)
+                .ok_or_else(|| self.error("invalid unicode escape"))?;
+            value = value * 16 + digit;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FilmlisteHeader, FilmlisteReader};
+    use crate::models::Item;
+
+    const FILMLISTE: &str = r#"{"Filmliste":["23.10.2023, 19:48","23.10.2023, 17:48","3","MSearch [Vers.: 3.1.219]","f00"],"Filmliste":["Sender","Thema","Titel","Datum","Zeit","Dauer","Größe [MB]","Beschreibung","Url","Website","Url Untertitel","Url RTMP","Url Klein","Url RTMP Klein","Url HD","Url RTMP HD","DatumL","Url History","Geo","neu"],
+"X":["3Sat","37 Grad","Liebe \"mit\" Hindernissen","01.10.2023","15:30:00","00:28:46","513","Beschreibung\nmit Ümlaut 😀","https://example.org/video/3sat_1_720p.mp4","https://www.3sat.de/video","https://example.org/sub.xml","","33|360p.mp4","","33|hd.mp4","","1696167000","","DE-AT-CH","false"],
+"X":["","","Folge 2","02.10.2023","15:30:00","","","","https://example.org/video/3sat_2.mp4","https://www.3sat.de/video2","","","","","https://other.example.org/hd.mp4","","1696253400","","","false"],
+"X":["ARD","Livestream","Das Erste Livestream","","","","","","https://example.org/live.m3u8","https://www.ardmediathek.de/live","","","","","","","","","",""]}"#;
+
+    #[test]
+    fn test_filmliste() -> Result<(), Box<dyn std::error::Error>> {
+        let mut reader = FilmlisteReader::new(FILMLISTE.as_bytes())?;
+        assert_eq!(
+            reader.header(),
+            &FilmlisteHeader {
+                timestamp: 1698083280,
+                version: "3".into(),
+                program: "MSearch [Vers.: 3.1.219]".into(),
+                id: "f00".into(),
+            }
+        );
+
+        let item = reader.next().unwrap()?;
+        assert_eq!(
+            item,
+            Item {
+                channel: "3Sat".into(),
+                topic: "37 Grad".into(),
+                title: "Liebe \"mit\" Hindernissen".into(),
+                description: Some("Beschreibung\nmit Ümlaut 😀".into()),
+                timestamp: 1696167000,
+                duration: Some(Duration::from_secs(28 * 60 + 46)),
+                size: Some(513 * 1024 * 1024),
+                url_website: "https://www.3sat.de/video".into(),
+                url_subtitle: Some("https://example.org/sub.xml".into()),
+                url_video: "https://example.org/video/3sat_1_720p.mp4".into(),
+                url_video_low: Some("https://example.org/video/3sat_1_360p.mp4".into()),
+                url_video_hd: Some("https://example.org/video/3sat_1_hd.mp4".into()),
+                filmliste_timestamp: 1698083280,
+                id: item.id.clone(),
+            }
+        );
+
+        let item = reader.next().unwrap()?;
+        assert_eq!(item.channel, "3Sat");
+        assert_eq!(item.topic, "37 Grad");
+        assert_eq!(item.duration, None);
+        assert_eq!(item.size, None);
+        assert_eq!(item.url_video_low, None);
+        assert_eq!(
+            item.url_video_hd.as_deref(),
+            Some("https://other.example.org/hd.mp4")
+        );
+
+        let item = reader.next().unwrap()?;
+        assert_eq!(item.channel, "ARD");
+        assert_eq!(item.topic, "Livestream");
+        assert_eq!(item.timestamp, 0);
+
+        assert!(reader.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_values() -> Result<(), Box<dyn std::error::Error>> {
+        for date in [
+            "31.02.2023, 17:48",
+            "23.10.2023, 24:00",
+            "23.10.2023, 99999999999999999:48",
+            "23.10.99999, 17:48",
+        ] {
+            let filmliste = FILMLISTE.replacen("23.10.2023, 17:48", date, 1);
+            assert!(
+                matches!(
+                    FilmlisteReader::new(filmliste.as_bytes()),
+                    Err(crate::Error::Filmliste(_))
+                ),
+                "{date}"
+            );
+        }
+
+        let filmliste = FILMLISTE
+            .replacen("00:28:46", "99999999999999999:00:00", 1)
+            .replacen(r#""513""#, r#""17592186044416""#, 1);
+        let item = FilmlisteReader::new(filmliste.as_bytes())?
+            .next()
+            .unwrap()?;
+        assert_eq!(item.duration, None);
+        assert_eq!(item.size, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_filmliste_ids() -> Result<(), Box<dyn std::error::Error>> {
+        let ids = FilmlisteReader::new(FILMLISTE.as_bytes())?
+            .map(|item| item.map(|item| item.id))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(ids.len(), 3);
+        assert!(ids.iter().all(|id| id.len() == 16));
+        assert_ne!(ids[0], ids[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_filmliste_error() -> Result<(), Box<dyn std::error::Error>> {
+        let truncated = &FILMLISTE[..FILMLISTE.len() - 30];
+        let items = FilmlisteReader::new(truncated.as_bytes())?.collect::<Vec<_>>();
+        assert_eq!(items.len(), 3);
+        assert!(items[..2].iter().all(Result::is_ok));
+        assert!(matches!(items[2], Err(crate::Error::Filmliste(_))));
+        Ok(())
+    }
+
+    #[cfg(feature = "xz")]
+    #[test]
+    fn test_filmliste_xz() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Read;
+
+        let mut compressed = Vec::new();
+        liblzma::read::XzEncoder::new(FILMLISTE.as_bytes(), 6).read_to_end(&mut compressed)?;
+
+        let items =
+            FilmlisteReader::new_xz(compressed.as_slice())?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(items.len(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! Client-side filtering of results, modelled on MediathekView's blacklist.
+//!
+//! The server can't exclude search terms, so results can be filtered
+//! afterwards using a [`Filter`]. A filter consists of [`Rule`]s that match
+//! the channel, topic and title of an item using regular expressions. The
+//! first matching rule decides whether an item is included or excluded. Items
+//! that don't match any rule are kept in [blacklist](FilterMode::Blacklist)
+//! mode and removed in [whitelist](FilterMode::Whitelist) mode.
+//!
+//! Filters can be (de)serialized using serde, e.g. to keep them in a
+//! configuration file:
+//!
+//! ```rust
+//! use mediathekviewweb::filter::Filter;
+//!
+//! let filter: Filter = serde_json::from_str(
+//!     r#"{
+//!         "min_duration": 300,
+//!         "rules": [
+//!             { "action": "include", "topic": "^tagesschau$" },
+//!             { "channel": "ARD", "topic": "tagesschau" },
+//!             { "title": "Hörfassung|Gebärdensprache" }
+//!         ]
+//!     }"#,
+//! )
+//! .unwrap();
+//! ```
+
+use std::{borrow::Borrow, str::FromStr, time::Duration};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::models::{Item, QueryResult};
+
+/// A set of rules that decide which items are kept.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Filter {
+    pub mode: FilterMode,
+    /// The rules in the order in which they are checked.
+    pub rules: Vec<Rule>,
+    /// Items that are shorter than this are always removed. Items without a
+    /// duration, e.g. livestreams, are kept.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::models::optional_duration_secs"
+    )]
+    pub min_duration: Option<Duration>,
+    /// Items that are longer than this are always removed. Items without a
+    /// duration, e.g. livestreams, are kept.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::models::optional_duration_secs"
+    )]
+    pub max_duration: Option<Duration>,
+}
+impl Filter {
+    /// Create an empty filter in blacklist mode that keeps all items.
+    pub fn blacklist() -> Self {
+        Self::default()
+    }
+    /// Create an empty filter in whitelist mode that removes all items.
+    pub fn whitelist() -> Self {
+        Self {
+            mode: FilterMode::Whitelist,
+            ..Self::default()
+        }
+    }
+    /// Add a rule, which is checked after the existing rules.
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+    /// Remove items that are shorter than `min_duration`.
+    pub fn min_duration(mut self, min_duration: Duration) -> Self {
+        self.min_duration = Some(min_duration);
+        self
+    }
+    /// Remove items that are longer than `max_duration`.
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// Check whether an item is kept by this filter.
+    pub fn accepts(&self, item: &Item) -> bool {
+        if let Some(duration) = item.duration {
+            if self.min_duration.is_some_and(|min| duration < min)
+                || self.max_duration.is_some_and(|max| duration > max)
+            {
+                return false;
+            }
+        }
+        match self.rules.iter().find(|rule| rule.matches(item)) {
+            Some(rule) => rule.action == Action::Include,
+            None => self.mode == FilterMode::Blacklist,
+        }
+    }
+
+    /// Remove the items that aren't accepted from a query result.
+    ///
+    /// The [`result_count`](crate::models::QueryInfo::result_count) is
+    /// updated, the `total_results` reported by the server are left as is.
+    pub fn apply(&self, result: &mut QueryResult) {
+        result.results.retain(|item| self.accepts(item));
+        result.query_info.result_count = result.results.len();
+    }
+
+    /// Filter an iterator of items or references to items.
+    pub fn filter<'a, I>(&'a self, items: I) -> impl Iterator<Item = I::Item> + 'a
+    where
+        I: IntoIterator + 'a,
+        I::Item: Borrow<Item>,
+    {
+        items.into_iter().filter(|item| self.accepts(item.borrow()))
+    }
+}
+
+/// Decides what happens to items that don't match any rule.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterMode {
+    /// Keep items that don't match any rule.
+    #[default]
+    Blacklist,
+    /// Remove items that don't match any rule.
+    Whitelist,
+}
+
+/// What happens to an item that matches a [`Rule`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    #[default]
+    Exclude,
+    Include,
+}
+
+/// A rule that matches items whose fields match all of the specified
+/// patterns.
+///
+/// A rule without any patterns matches all items.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Rule {
+    pub action: Action,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<Pattern>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<Pattern>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<Pattern>,
+}
+impl Rule {
+    /// Create a rule that excludes matching items.
+    pub fn exclude() -> Self {
+        Self::default()
+    }
+    /// Create a rule that includes matching items.
+    pub fn include() -> Self {
+        Self {
+            action: Action::Include,
+            ..Self::default()
+        }
+    }
+    /// Only match items whose channel matches `pattern`.
+    pub fn channel(mut self, pattern: Pattern) -> Self {
+        self.channel = Some(pattern);
+        self
+    }
+    /// Only match items whose topic matches `pattern`.
+    pub fn topic(mut self, pattern: Pattern) -> Self {
+        self.topic = Some(pattern);
+        self
+    }
+    /// Only match items whose title matches `pattern`.
+    pub fn title(mut self, pattern: Pattern) -> Self {
+        self.title = Some(pattern);
+        self
+    }
+
+    /// Check whether an item matches all patterns of this rule.
+    pub fn matches(&self, item: &Item) -> bool {
+        [
+            (&self.channel, item.channel.as_str()),
+            (&self.topic, item.topic.as_str()),
+            (&self.title, item.title.as_str()),
+        ]
+        .into_iter()
+        .all(|(pattern, field)| pattern.as_ref().is_none_or(|p| p.is_match(field)))
+    }
+}
+
+/// A case-insensitive regular expression that matches anywhere in a field.
+///
+/// Use `^` and `$` to match the whole field. It is serialized as the original
+/// pattern.
+#[derive(Clone, Debug)]
+pub struct Pattern(Regex);
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map(Self)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+}
+impl FromStr for Pattern {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_str().serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Self::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Filter, FilterMode, Rule};
+    use crate::{
+        mock::fixtures,
+        models::{Item, QueryResult},
+    };
+
+    fn titles<'a>(items: impl Iterator<Item = &'a Item>) -> Vec<&'a str> {
+        items.map(|item| item.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_blacklist() -> Result<(), regex::Error> {
+        let items = fixtures();
+        let filter = Filter::blacklist()
+            .rule(Rule::exclude().topic("tagesschau".parse()?))
+            .rule(
+                Rule::exclude()
+                    .channel("^zdf$".parse()?)
+                    .title("livestream".parse()?),
+            )
+            .min_duration(Duration::from_secs(30 * 60));
+        assert_eq!(
+            titles(filter.filter(&items)),
+            [
+                "Folge 4000",
+                "Das Erste Livestream",
+                "heute journal vom 2. Oktober 2023",
+                "Die Zukunft des Fernsehens"
+            ]
+        );
+
+        let mut result = QueryResult {
+            query_info: crate::models::QueryInfo {
+                filmliste_timestamp: 0,
+                result_count: items.len(),
+                search_engine_time: Duration::ZERO,
+                total_results: 100,
+            },
+            results: items,
+        };
+        filter.apply(&mut result);
+        assert_eq!(result.query_info.result_count, 4);
+        assert_eq!(result.query_info.total_results, 100);
+        Ok(())
+    }
+
+    #[test]
+    fn test_whitelist() -> Result<(), regex::Error> {
+        let items = fixtures();
+        let filter = Filter::whitelist()
+            .rule(Rule::exclude().title("Gebärdensprache".parse()?))
+            .rule(Rule::include().topic("^TAGESSCHAU".parse()?))
+            .max_duration(Duration::from_secs(910));
+        assert_eq!(
+            titles(filter.filter(items.iter())),
+            ["tagesschau 20:00 Uhr", "tagesschau 12:00 Uhr"]
+        );
+        assert!(Filter::whitelist().filter(&items).next().is_none());
+        assert_eq!(
+            Filter::blacklist().filter(items.clone()).count(),
+            items.len()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_serde() -> Result<(), Box<dyn std::error::Error>> {
+        let filter: Filter = serde_json::from_str(
+            r#"{
+                "mode": "whitelist",
+                "min_duration": 300,
+                "rules": [{ "action": "include", "channel": "ARD|ZDF" }, {}]
+            }"#,
+        )?;
+        assert_eq!(filter.mode, FilterMode::Whitelist);
+        assert_eq!(filter.min_duration, Some(Duration::from_secs(300)));
+        assert_eq!(
+            filter.rules,
+            [Rule::include().channel("ARD|ZDF".parse()?), Rule::exclude()]
+        );
+        assert_eq!(
+            serde_json::to_string(&filter)?,
+            r#"{"mode":"whitelist","rules":[{"action":"include","channel":"ARD|ZDF"},{"action":"exclude"}],"min_duration":300}"#
+        );
+
+        assert!(serde_json::from_str::<Filter>(r#"{"rules": [{"title": "("}]}"#).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,480 @@
+//! Support for HLS (HTTP Live Streaming) playlists.
+//!
+//! Livestreams and some adaptive videos are provided as `.m3u8` playlists
+//! instead of a single video file. There are two kinds of playlists:
+//!
+//! - A [`MasterPlaylist`] lists the available [`Variant`]s of a stream, e.g.
+//!   different resolutions, and alternative [`Rendition`]s like audio tracks.
+//! - A [`MediaPlaylist`] lists the [`Segment`]s of a single variant, which are
+//!   played one after another.
+//!
+//! With the `download` feature, [`Downloader::download_hls`](crate::download::Downloader::download_hls)
+//! downloads the segments of a variant and concatenates them into a single
+//! file.
+//!
+//! # Example
+//! ```rust
+//! use mediathekviewweb::hls::{Playlist, VariantSelection};
+//!
+//! let playlist: Playlist = "#EXTM3U
+//! #EXT-X-STREAM-INF:BANDWIDTH=1280000,RESOLUTION=640x360,CODECS=\"avc1.4d401e,mp4a.40.2\"
+//! low/index.m3u8
+//! #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080,CODECS=\"avc1.640028,mp4a.40.2\"
+//! high/index.m3u8
+//! "
+//! .parse()
+//! .unwrap();
+//!
+//! let Playlist::Master(master) = playlist else {
+//!     panic!("not a master playlist")
+//! };
+//! let variant = master.select(VariantSelection::MaxHeight(720)).unwrap();
+//! assert_eq!(variant.uri, "low/index.m3u8");
+//! ```
+
+use std::{fmt::Display, str::FromStr, time::Duration};
+
+use crate::models::Item;
+
+/// Check whether a URL points to an HLS playlist.
+pub fn is_hls_url(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.get(path.len().saturating_sub(5)..)
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(".m3u8"))
+}
+
+impl Item {
+    /// Check whether the video of this item is an HLS playlist, e.g. for
+    /// livestreams.
+    pub fn is_hls(&self) -> bool {
+        is_hls_url(&self.url_video)
+    }
+}
+
+/// An error caused by a malformed playlist.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlaylistError {
+    /// The line number (starting at 1) where the error occured.
+    pub line: usize,
+    /// A description of the error.
+    pub message: String,
+}
+impl Display for PlaylistError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} in line {}", self.message, self.line)
+    }
+}
+impl std::error::Error for PlaylistError {}
+
+/// A parsed HLS playlist.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Playlist {
+    Master(MasterPlaylist),
+    Media(MediaPlaylist),
+}
+
+/// A playlist that lists the variants of a stream.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MasterPlaylist {
+    pub variants: Vec<Variant>,
+    /// Alternative renditions, e.g. audio tracks in other languages.
+    pub renditions: Vec<Rendition>,
+}
+impl MasterPlaylist {
+    /// Select a variant.
+    ///
+    /// Returns `None` if the playlist doesn't contain any variants or no
+    /// variant matches the selection.
+    pub fn select(&self, selection: VariantSelection) -> Option<&Variant> {
+        match selection {
+            VariantSelection::Highest => self.variants.iter().max_by_key(|v| v.bandwidth),
+            VariantSelection::Lowest => self.variants.iter().min_by_key(|v| v.bandwidth),
+            VariantSelection::MaxHeight(max_height) => self
+                .variants
+                .iter()
+                .filter(|v| v.resolution.is_some_and(|r| r.height <= max_height))
+                .max_by_key(|v| v.bandwidth),
+        }
+    }
+}
+
+/// Describes which variant of a [`MasterPlaylist`] is selected.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum VariantSelection {
+    /// The variant with the highest bandwidth.
+    #[default]
+    Highest,
+    /// The variant with the lowest bandwidth.
+    Lowest,
+    /// The variant with the highest bandwidth whose height is at most the
+    /// specified count of pixels.
+    MaxHeight(u32),
+}
+
+/// A variant of a stream (`EXT-X-STREAM-INF`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Variant {
+    /// The URI of the variant's media playlist, possibly relative to the
+    /// master playlist.
+    pub uri: String,
+    /// The peak bandwidth in bits per second.
+    pub bandwidth: u64,
+    /// The average bandwidth in bits per second.
+    pub average_bandwidth: Option<u64>,
+    pub resolution: Option<Resolution>,
+    pub frame_rate: Option<f64>,
+    /// The codecs of the variant, e.g. `avc1.640028` and `mp4a.40.2`.
+    pub codecs: Vec<String>,
+    /// The group ID of the audio renditions that can be used with this
+    /// variant.
+    pub audio: Option<String>,
+}
+
+/// The resolution of a [`Variant`] in pixels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// An alternative rendition of a stream (`EXT-X-MEDIA`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Rendition {
+    /// The type of the rendition, e.g. `AUDIO` or `SUBTITLES`.
+    pub kind: String,
+    pub group_id: String,
+    pub name: String,
+    pub language: Option<String>,
+    /// The URI of the rendition's media playlist. If it is missing, the
+    /// rendition is included in the variants' streams.
+    pub uri: Option<String>,
+    pub default: bool,
+}
+
+/// A playlist that lists the segments of a single variant.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MediaPlaylist {
+    /// The maximum duration of a segment.
+    pub target_duration: Duration,
+    /// The sequence number of the first segment.
+    pub media_sequence: u64,
+    pub segments: Vec<Segment>,
+    /// The URI of a segment that must be played before all other segments
+    /// (`EXT-X-MAP`).
+    pub init_segment: Option<String>,
+    /// Whether the segments are encrypted (`EXT-X-KEY`).
+    pub encrypted: bool,
+    /// Whether the playlist is complete (`EXT-X-ENDLIST`). Playlists of
+    /// livestreams are incomplete and list only the most recent segments.
+    pub complete: bool,
+}
+impl MediaPlaylist {
+    /// The total duration of all segments.
+    pub fn duration(&self) -> Duration {
+        self.segments.iter().map(|segment| segment.duration).sum()
+    }
+}
+
+/// A segment of a [`MediaPlaylist`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    /// The URI of the segment, possibly relative to the media playlist.
+    pub uri: String,
+    pub duration: Duration,
+}
+
+impl FromStr for Playlist {
+    type Err = PlaylistError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        match lines.next() {
+            Some((_, line)) if line.trim_start_matches('\u{feff}') == "#EXTM3U" => {}
+            Some((line, _)) => return Err(error(line, "missing #EXTM3U header")),
+            None => return Err(error(1, "empty playlist")),
+        }
+
+        let mut master = MasterPlaylist::default();
+        let mut media = MediaPlaylist::default();
+        let mut pending_variant = None;
+        let mut pending_duration = None;
+
+        for (number, line) in lines {
+            if let Some(tag) = line.strip_prefix('#') {
+                let (name, value) = tag.split_once(':').unwrap_or((tag, ""));
+                match name {
+                    "EXT-X-STREAM-INF" => pending_variant = Some(parse_variant(number, value)?),
+                    "EXT-X-MEDIA" => master.renditions.push(parse_rendition(number, value)?),
+                    "EXT-X-TARGETDURATION" => {
+                        media.target_duration =
+                            Duration::from_secs(parse_number(number, name, value)?)
+                    }
+                    "EXT-X-MEDIA-SEQUENCE" => {
+                        media.media_sequence = parse_number(number, name, value)?
+                    }
+                    "EXTINF" => {
+                        let duration = value.split(',').next().unwrap_or_default().trim();
+                        pending_duration = Some(
+                            duration
+                                .parse()
+                                .ok()
+                                .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                                .ok_or_else(|| error(number, "invalid segment duration"))?,
+                        );
+                    }
+                    "EXT-X-KEY" => {
+                        media.encrypted |=
+                            attributes(value).any(|(key, value)| key == "METHOD" && value != "NONE")
+                    }
+                    "EXT-X-MAP" => {
+                        media.init_segment = Some(
+                            attribute(value, "URI")
+                                .ok_or_else(|| error(number, "missing URI in EXT-X-MAP"))?,
+                        )
+                    }
+                    "EXT-X-ENDLIST" => media.complete = true,
+                    // comments and unsupported tags
+                    _ => {}
+                }
+            } else if let Some(mut variant) = pending_variant.take() {
+                variant.uri = line.to_owned();
+                master.variants.push(variant);
+            } else if let Some(duration) = pending_duration.take() {
+                media.segments.push(Segment {
+                    uri: line.to_owned(),
+                    duration,
+                });
+            } else {
+                return Err(error(number, "URI without a preceding tag"));
+            }
+        }
+
+        if !master.variants.is_empty() {
+            Ok(Playlist::Master(master))
+        } else {
+            Ok(Playlist::Media(media))
+        }
+    }
+}
+
+fn error(line: usize, message: impl Into<String>) -> PlaylistError {
+    PlaylistError {
+        line,
+        message: message.into(),
+    }
+}
+
+fn parse_number<T: FromStr>(line: usize, name: &str, value: &str) -> Result<T, PlaylistError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| error(line, format!("invalid value of {name}")))
+}
+
+fn parse_variant(line: usize, value: &str) -> Result<Variant, PlaylistError> {
+    let mut variant = Variant::default();
+    let mut has_bandwidth = false;
+    for (key, value) in attributes(value) {
+        match key {
+            "BANDWIDTH" => {
+                variant.bandwidth = parse_number(line, key, &value)?;
+                has_bandwidth = true;
+            }
+            "AVERAGE-BANDWIDTH" => {
+                variant.average_bandwidth = Some(parse_number(line, key, &value)?)
+            }
+            "RESOLUTION" => {
+                variant.resolution = Some(
+                    value
+                        .split_once('x')
+                        .and_then(|(width, height)| {
+                            Some(Resolution {
+                                width: width.parse().ok()?,
+                                height: height.parse().ok()?,
+                            })
+                        })
+                        .ok_or_else(|| error(line, "invalid value of RESOLUTION"))?,
+                )
+            }
+            "FRAME-RATE" => variant.frame_rate = Some(parse_number(line, key, &value)?),
+            "CODECS" => {
+                variant.codecs = value
+                    .split(',')
+                    .map(|codec| codec.trim().to_owned())
+                    .collect()
+            }
+            "AUDIO" => variant.audio = Some(value),
+            _ => {}
+        }
+    }
+    if !has_bandwidth {
+        return Err(error(line, "missing BANDWIDTH in EXT-X-STREAM-INF"));
+    }
+    Ok(variant)
+}
+
+fn parse_rendition(line: usize, value: &str) -> Result<Rendition, PlaylistError> {
+    let mut rendition = Rendition::default();
+    for (key, value) in attributes(value) {
+        match key {
+            "TYPE" => rendition.kind = value,
+            "GROUP-ID" => rendition.group_id = value,
+            "NAME" => rendition.name = value,
+            "LANGUAGE" => rendition.language = Some(value),
+            "URI" => rendition.uri = Some(value),
+            "DEFAULT" => rendition.default = value == "YES",
+            _ => {}
+        }
+    }
+    if rendition.kind.is_empty() || rendition.group_id.is_empty() {
+        return Err(error(line, "missing TYPE or GROUP-ID in EXT-X-MEDIA"));
+    }
+    Ok(rendition)
+}
+
+fn attribute(list: &str, name: &str) -> Option<String> {
+    attributes(list).find_map(|(key, value)| (key == name).then_some(value))
+}
+
+/// Parses an attribute list like `KEY=VALUE,KEY="quoted, value"`.
+fn attributes(mut list: &str) -> impl Iterator<Item = (&str, String)> {
+    std::iter::from_fn(move || {
+        let (key, rest) = list.split_once('=')?;
+        let (value, rest) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let (value, rest) = quoted.split_once('"').unwrap_or((quoted, ""));
+                (value, rest.split_once(',').map_or("", |(_, rest)| rest))
+            }
+            None => rest.split_once(',').unwrap_or((rest, "")),
+        };
+        list = rest;
+        Some((key.trim(), value.to_owned()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{is_hls_url, Playlist, PlaylistError, Resolution, Segment, VariantSelection};
+
+    #[test]
+    fn test_master_playlist() {
+        let playlist: Playlist = "#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"audio\",NAME=\"Deutsch\",LANGUAGE=\"de\",DEFAULT=YES
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"audio\",NAME=\"Audiodeskription\",LANGUAGE=\"de\",URI=\"ad/index.m3u8\"
+#EXT-X-STREAM-INF:BANDWIDTH=5000000,AVERAGE-BANDWIDTH=4500000,RESOLUTION=1920x1080,FRAME-RATE=50.000,CODECS=\"avc1.640028,mp4a.40.2\",AUDIO=\"audio\"
+1080/index.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=1800000,RESOLUTION=960x540,CODECS=\"avc1.4d401f,mp4a.40.2\",AUDIO=\"audio\"
+540/index.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=3000000,RESOLUTION=1280x720,CODECS=\"avc1.4d401f,mp4a.40.2\",AUDIO=\"audio\"
+https://example.com/720/index.m3u8
+"
+        .parse()
+        .unwrap();
+        let Playlist::Master(master) = playlist else {
+            panic!("expected a master playlist")
+        };
+
+        assert_eq!(master.variants.len(), 3);
+        let variant = &master.variants[0];
+        assert_eq!(variant.uri, "1080/index.m3u8");
+        assert_eq!(variant.bandwidth, 5000000);
+        assert_eq!(variant.average_bandwidth, Some(4500000));
+        assert_eq!(
+            variant.resolution,
+            Some(Resolution {
+                width: 1920,
+                height: 1080
+            })
+        );
+        assert_eq!(variant.frame_rate, Some(50.0));
+        assert_eq!(variant.codecs, ["avc1.640028", "mp4a.40.2"]);
+        assert_eq!(variant.audio.as_deref(), Some("audio"));
+
+        assert_eq!(master.renditions.len(), 2);
+        assert!(master.renditions[0].default);
+        assert_eq!(master.renditions[0].uri, None);
+        assert_eq!(master.renditions[1].name, "Audiodeskription");
+        assert_eq!(master.renditions[1].uri.as_deref(), Some("ad/index.m3u8"));
+
+        let uri = |selection| master.select(selection).map(|v| v.uri.as_str());
+        assert_eq!(uri(VariantSelection::Highest), Some("1080/index.m3u8"));
+        assert_eq!(uri(VariantSelection::Lowest), Some("540/index.m3u8"));
+        assert_eq!(
+            uri(VariantSelection::MaxHeight(720)),
+            Some("https://example.com/720/index.m3u8")
+        );
+        assert_eq!(uri(VariantSelection::MaxHeight(360)), None);
+    }
+
+    #[test]
+    fn test_media_playlist() {
+        let playlist: Playlist = "#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXT-X-MEDIA-SEQUENCE:42
+#EXT-X-MAP:URI=\"init.mp4\"
+#EXTINF:6.000,
+segment42.m4s
+#EXTINF:5.5,Title
+segment43.m4s
+#EXT-X-ENDLIST
+"
+        .parse()
+        .unwrap();
+        let Playlist::Media(media) = playlist else {
+            panic!("expected a media playlist")
+        };
+
+        assert_eq!(media.target_duration, Duration::from_secs(6));
+        assert_eq!(media.media_sequence, 42);
+        assert_eq!(media.init_segment.as_deref(), Some("init.mp4"));
+        assert_eq!(
+            media.segments,
+            [
+                Segment {
+                    uri: "segment42.m4s".into(),
+                    duration: Duration::from_secs(6)
+                },
+                Segment {
+                    uri: "segment43.m4s".into(),
+                    duration: Duration::from_millis(5500)
+                }
+            ]
+        );
+        assert_eq!(media.duration(), Duration::from_millis(11500));
+        assert!(media.complete);
+        assert!(!media.encrypted);
+
+        assert_eq!(
+            "#EXTM3U\n#EXTINF:abc,\nsegment.ts".parse::<Playlist>(),
+            Err(PlaylistError {
+                line: 2,
+                message: "invalid segment duration".into()
+            })
+        );
+        assert_eq!(
+            "segment.ts".parse::<Playlist>(),
+            Err(PlaylistError {
+                line: 1,
+                message: "missing #EXTM3U header".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_is_hls_url() {
+        assert!(is_hls_url(
+            "https://daserste-live.ard-mcdn.de/daserste/live/hls/de/master.m3u8"
+        ));
+        assert!(is_hls_url("https://example.com/index.M3U8?token=abc"));
+        assert!(!is_hls_url("https://example.com/video.mp4"));
+        assert!(!is_hls_url("https://example.com/m3u8/video.mp4"));
+    }
+}
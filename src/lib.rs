@@ -41,29 +41,67 @@
 use std::{
     future::{Future, IntoFuture},
     pin::Pin,
-    time::Duration,
+    sync::Arc,
+    time::{Duration, SystemTime},
 };
 
-use reqwest::header::HeaderMap;
-use serde::Serialize;
+use futures_core::Stream;
+use futures_util::StreamExt;
 
 pub use crate::error::{Error, Result};
-use crate::models::{ApiResult, Query, QueryField, QueryResult, SortField, SortOrder};
+use crate::{
+    cache::{CacheStats, QueryCache},
+    emulation::{Emulation, Merge},
+    models::{
+        ApiResult, Channel, Item, MediathekQuery, Query, QueryField, QueryResult, SortField,
+        SortOrder,
+    },
+    retry::{RateLimiter, RetryPolicy},
+    search_string::{ParseError, SearchString, Term},
+    transport::{Method, Request, Response, Transport},
+};
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cache;
+mod date;
+pub mod datetime;
+#[cfg(feature = "download")]
+pub mod download;
+mod emulation;
 mod error;
+#[cfg(feature = "feed")]
+pub mod feed;
+pub mod filmliste;
+#[cfg(feature = "filter")]
+pub mod filter;
+pub mod hls;
+pub mod local;
+pub mod m3u;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod models;
+pub mod retry;
+pub mod search_string;
+pub mod subscriptions;
+#[cfg(feature = "subtitles")]
+pub mod subtitles;
+pub mod transport;
 
 /// A client for a MediathekViewWeb server.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Mediathek {
-    base_url: String,
-    http: reqwest::Client,
+    transport: Arc<dyn Transport>,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    cache: Option<Arc<QueryCache>>,
 }
 impl Mediathek {
     /// Create a new client for the official server hosted at <https://mediathekviewweb.de>.
     ///
     /// `user_agent` identifies your application so the server administrators
     /// can contact you in case of a problem.
+    #[cfg(feature = "reqwest")]
     pub fn new(user_agent: reqwest::header::HeaderValue) -> crate::Result<Self> {
         Self::new_with_url("https://mediathekviewweb.de", user_agent)
     }
@@ -73,25 +111,65 @@ impl Mediathek {
     ///
     /// `user_agent` identifies your application so the server administrators
     /// can contact you in case of a problem.
+    #[cfg(feature = "reqwest")]
     pub fn new_with_url(
         base_url: impl Into<String>,
         user_agent: reqwest::header::HeaderValue,
     ) -> crate::Result<Self> {
-        let mut base_url: String = base_url.into();
-        if base_url.ends_with('/') {
-            base_url.truncate(base_url.len() - 1)
+        Self::builder()
+            .base_url(base_url)
+            .user_agent(user_agent)
+            .build()
+    }
+
+    /// Create a [`MediathekBuilder`] to configure a new client.
+    #[cfg(feature = "reqwest")]
+    pub fn builder() -> MediathekBuilder {
+        MediathekBuilder::default()
+    }
+
+    /// Create a new client that uses a custom [`Transport`] to communicate
+    /// with the server.
+    pub fn with_transport(transport: impl Transport + 'static) -> Self {
+        Self {
+            transport: Arc::new(transport),
+            retry_policy: RetryPolicy::never(),
+            rate_limiter: None,
+            cache: None,
         }
+    }
 
-        Ok(Self {
-            base_url,
-            http: reqwest::Client::builder()
-                .default_headers({
-                    let mut headers = HeaderMap::new();
-                    headers.insert(reqwest::header::USER_AGENT, user_agent);
-                    headers
-                })
-                .build()?,
-        })
+    /// Retry failed requests according to a [`RetryPolicy`].
+    ///
+    /// By default, failed requests are not retried.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Limit the rate of requests sent to the server.
+    ///
+    /// The limit is shared with all clones of this client. By default, the
+    /// request rate is not limited.
+    pub fn with_rate_limit(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(Arc::new(rate_limiter));
+        self
+    }
+
+    /// Cache the results of queries using a [`QueryCache`].
+    ///
+    /// The cache is shared with all clones of this client. By default, results
+    /// are not cached. Paginated queries using
+    /// [`MediathekQueryBuilder::pages`] are cached page by page.
+    pub fn with_cache(mut self, cache: QueryCache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Return the hit and miss counts of the [cache](Self::with_cache), if
+    /// one is used.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats())
     }
 }
 impl Mediathek {
@@ -107,120 +185,383 @@ impl Mediathek {
     }
     /// Query the current media database by parsing a query string using
     /// [MediathekViewWeb's advanced search syntax](https://github.com/mediathekview/mediathekviewweb/blob/master/README.md#erweiterte-suche).
-    pub fn query_string(&self, query: &str, search_everywhere: bool) -> MediathekQueryBuilder<'_> {
+    ///
+    /// All free text terms are combined into a single query that searches in
+    /// the topic and title or, if `search_everywhere` is set, in all fields.
+    /// Negated (`-`) and alternative (`|`) text terms search in the same
+    /// fields, but each of them is a separate query.
+    ///
+    /// See [`search_string`] for details on the supported syntax.
+    pub fn query_string(
+        &self,
+        query: &str,
+        search_everywhere: bool,
+    ) -> crate::Result<MediathekQueryBuilder<'_>> {
+        Ok(MediathekQueryBuilder {
+            client: self,
+            query: MediathekQuery::from_search_string(query, search_everywhere)?,
+            limit: None,
+        })
+    }
+    /// Query the current media database using an already parsed
+    /// [`SearchString`].
+    ///
+    /// See [`query_string`](Self::query_string) for details.
+    pub fn query_search_string(
+        &self,
+        query: &SearchString,
+        search_everywhere: bool,
+    ) -> MediathekQueryBuilder<'_> {
         MediathekQueryBuilder {
             client: self,
-            query: MediathekQuery::from_search_string(query, search_everywhere),
+            query: MediathekQuery::from_parsed_search_string(query, search_everywhere),
+            limit: None,
         }
     }
+
+    /// Query the current media database using a prepared [`MediathekQuery`].
+    pub fn execute(&self, query: MediathekQuery) -> MediathekQueryBuilder<'_> {
+        MediathekQueryBuilder {
+            client: self,
+            query,
+            limit: None,
+        }
+    }
+
+    async fn send_query(&self, query: &MediathekQuery) -> crate::Result<QueryResult> {
+        if !query.needs_emulation() {
+            return self.send_server_query(query).await;
+        }
+        let mut emulation = Emulation::new(query);
+        while let Some(query) = emulation.next_query() {
+            let result = self.send_server_query(query).await?;
+            emulation.process(result);
+        }
+        Ok(emulation.finish())
+    }
+
+    /// Send a query that is supported by the server, using the cache if
+    /// available.
+    async fn send_server_query(&self, query: &MediathekQuery) -> crate::Result<QueryResult> {
+        if let Some(result) = self.cache.as_ref().and_then(|cache| cache.get(query)) {
+            return Ok(result);
+        }
+        let result = query_response(self.send_request(query_request(query)?).await?)?;
+        if let Some(cache) = &self.cache {
+            cache.insert(query, &result);
+        }
+        Ok(result)
+    }
+
+    /// Send a request, retrying it according to the retry policy.
+    ///
+    /// Unsuccessful responses are returned if they can't be retried.
+    async fn send_request(&self, request: Request) -> crate::Result<Response> {
+        let mut attempt = 1;
+        loop {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                let wait = rate_limiter.reserve();
+                if !wait.is_zero() {
+                    self.transport.sleep(wait).await;
+                }
+            }
+
+            let delay = match self.transport.send(request.clone()).await {
+                Ok(response) if response.is_success() => return Ok(response),
+                Ok(response) => {
+                    let retry_after = response
+                        .header("Retry-After")
+                        .and_then(retry::parse_retry_after);
+                    match self.retry_policy.delay(
+                        attempt,
+                        &Error::Status(response.status),
+                        retry_after,
+                    ) {
+                        Some(delay) => delay,
+                        None => return Ok(response),
+                    }
+                }
+                Err(e) => {
+                    let e = Error::Transport(e);
+                    match self.retry_policy.delay(attempt, &e, None) {
+                        Some(delay) => delay,
+                        None => return Err(e),
+                    }
+                }
+            };
+            self.transport.sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// A builder to configure a [`Mediathek`] client.
+///
+/// Created using [`Mediathek::builder`].
+///
+/// # Example
+/// ```rust
+/// # fn main() -> mediathekviewweb::Result<()> {
+/// use std::time::Duration;
+///
+/// use mediathekviewweb::{retry::RetryPolicy, Mediathek};
+///
+/// let mediathek = Mediathek::builder()
+///     .user_agent("example".try_into().unwrap())
+///     .timeout(Duration::from_secs(30))
+///     .retry_policy(RetryPolicy::new())
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "reqwest")]
+#[derive(Debug)]
+pub struct MediathekBuilder {
+    base_url: String,
+    headers: reqwest::header::HeaderMap,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    proxies: Vec<reqwest::Proxy>,
+    http: Option<reqwest::Client>,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
+    cache: Option<QueryCache>,
 }
+#[cfg(feature = "reqwest")]
+impl Default for MediathekBuilder {
+    fn default() -> Self {
+        Self {
+            base_url: "https://mediathekviewweb.de".into(),
+            headers: reqwest::header::HeaderMap::new(),
+            timeout: None,
+            connect_timeout: None,
+            proxies: Vec::new(),
+            http: None,
+            retry_policy: RetryPolicy::never(),
+            rate_limiter: None,
+            cache: None,
+        }
+    }
+}
+#[cfg(feature = "reqwest")]
+impl MediathekBuilder {
+    /// Use the MediathekViewWeb server hosted at a specific URL.
+    ///
+    /// Defaults to the official server hosted at <https://mediathekviewweb.de>.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+    /// Set the `User-Agent` header that identifies your application so the
+    /// server administrators can contact you in case of a problem.
+    pub fn user_agent(mut self, user_agent: reqwest::header::HeaderValue) -> Self {
+        self.headers.insert(reqwest::header::USER_AGENT, user_agent);
+        self
+    }
+    /// Add a header that is sent with every request.
+    pub fn header(
+        mut self,
+        name: reqwest::header::HeaderName,
+        value: reqwest::header::HeaderValue,
+    ) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+    /// Add headers that are sent with every request.
+    pub fn default_headers(mut self, headers: reqwest::header::HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+    /// Set a timeout for each request, from connecting until the response
+    /// body has been read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    /// Set a timeout for connecting to the server.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+    /// Send requests through a proxy.
+    ///
+    /// Can be called multiple times to add several proxies.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+    /// Use an existing [`reqwest::Client`], e.g. to share its connection pool
+    /// with other parts of your application.
+    ///
+    /// The client is used as it is, so the headers, timeouts and proxies of
+    /// this builder are ignored.
+    pub fn client(mut self, http: reqwest::Client) -> Self {
+        self.http = Some(http);
+        self
+    }
+    /// Retry failed requests according to a [`RetryPolicy`].
+    ///
+    /// See [`Mediathek::with_retry_policy`].
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+    /// Limit the rate of requests sent to the server.
+    ///
+    /// See [`Mediathek::with_rate_limit`].
+    pub fn rate_limit(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+    /// Cache the results of queries.
+    ///
+    /// See [`Mediathek::with_cache`].
+    pub fn cache(mut self, cache: QueryCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Build the client.
+    ///
+    /// Returns [`Error::InvalidBaseUrl`] if the base URL is not an absolute
+    /// `http` or `https` URL.
+    pub fn build(self) -> crate::Result<Mediathek> {
+        validate_base_url(&self.base_url)?;
 
-#[derive(Debug, Default, Serialize)]
-#[cfg_attr(test, derive(PartialEq))]
-struct MediathekQuery {
-    queries: Vec<Query>,
-    #[serde(
-        skip_serializing_if = "Option::is_none",
-        serialize_with = "duration_as_seconds"
-    )]
-    duration_min: Option<Duration>,
-    #[serde(
-        skip_serializing_if = "Option::is_none",
-        serialize_with = "duration_as_seconds"
-    )]
-    duration_max: Option<Duration>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    future: Option<bool>,
-    #[serde(rename = "sortBy", skip_serializing_if = "Option::is_none")]
-    sort_by: Option<SortField>,
-    #[serde(rename = "sortOrder", skip_serializing_if = "Option::is_none")]
-    sort_order: Option<SortOrder>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    size: Option<usize>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    offset: Option<usize>,
+        let http = match self.http {
+            Some(http) => http,
+            None => {
+                let mut builder = reqwest::Client::builder().default_headers(self.headers);
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(connect_timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(connect_timeout);
+                }
+                for proxy in self.proxies {
+                    builder = builder.proxy(proxy);
+                }
+                builder.build()?
+            }
+        };
+
+        Ok(Mediathek {
+            transport: Arc::new(transport::ReqwestTransport::new(self.base_url, http)),
+            retry_policy: self.retry_policy,
+            rate_limiter: self.rate_limiter.map(Arc::new),
+            cache: self.cache.map(Arc::new),
+        })
+    }
 }
 
-fn duration_as_seconds<S: serde::Serializer>(
-    duration: &Option<Duration>,
-    serializer: S,
-) -> std::result::Result<S::Ok, S::Error> {
-    duration
-        .as_ref()
-        .map(Duration::as_secs)
-        .serialize(serializer)
+/// Check that a base URL is an absolute `http` or `https` URL without a query
+/// or fragment.
+#[cfg(feature = "reqwest")]
+fn validate_base_url(base_url: &str) -> crate::Result<()> {
+    match reqwest::Url::parse(base_url) {
+        Ok(url)
+            if matches!(url.scheme(), "http" | "https")
+                && url.has_host()
+                && url.query().is_none()
+                && url.fragment().is_none() =>
+        {
+            Ok(())
+        }
+        _ => Err(Error::InvalidBaseUrl(base_url.to_owned())),
+    }
 }
 
 impl MediathekQuery {
-    fn from_search_string(s: &str, search_everywhere: bool) -> Self {
+    /// Create a query by parsing a query string using
+    /// [MediathekViewWeb's advanced search syntax](https://github.com/mediathekview/mediathekviewweb/blob/master/README.md#erweiterte-suche).
+    ///
+    /// See [`Mediathek::query_string`] for details.
+    pub fn from_search_string(
+        s: &str,
+        search_everywhere: bool,
+    ) -> std::result::Result<Self, ParseError> {
+        Ok(Self::from_parsed_search_string(
+            &s.parse()?,
+            search_everywhere,
+        ))
+    }
+
+    /// Create a query from an already parsed [`SearchString`].
+    ///
+    /// See [`Mediathek::query_string`] for details.
+    pub fn from_parsed_search_string(search: &SearchString, search_everywhere: bool) -> Self {
+        let text_fields = if search_everywhere {
+            vec![
+                QueryField::Channel,
+                QueryField::Topic,
+                QueryField::Title,
+                QueryField::Description,
+            ]
+        } else {
+            vec![QueryField::Topic, QueryField::Title]
+        };
         let mut query = Self::default();
+        let mut text = Vec::new();
 
-        for part in s.split_whitespace() {
-            if let Some(channel) = part.strip_prefix('!') {
-                query.queries.push(Query {
-                    fields: vec![QueryField::Channel],
-                    query: channel.replace(',', " "),
-                })
-            } else if let Some(topic) = part.strip_prefix('#') {
-                query.queries.push(Query {
-                    fields: vec![QueryField::Topic],
-                    query: topic.replace(',', " "),
-                })
-            } else if let Some(title) = part.strip_prefix('+') {
-                query.queries.push(Query {
-                    fields: vec![QueryField::Title],
-                    query: title.replace(',', " "),
-                })
-            } else if let Some(description) = part.strip_prefix('*') {
-                query.queries.push(Query {
-                    fields: vec![QueryField::Description],
-                    query: description.replace(',', " "),
-                })
-            } else if let Some(duration_min) = part
-                .strip_prefix('>')
-                .and_then(|s| s.parse().ok())
-                .map(|minutes: u64| Duration::from_secs(minutes * 60))
-            {
-                query.duration_min = Some(duration_min)
-            } else if let Some(duration_max) = part
-                .strip_prefix('<')
-                .and_then(|s| s.parse().ok())
-                .map(|minutes: u64| Duration::from_secs(minutes * 60))
-            {
-                query.duration_max = Some(duration_max)
-            } else {
-                let fields = if search_everywhere {
-                    vec![
-                        QueryField::Channel,
-                        QueryField::Topic,
-                        QueryField::Title,
-                        QueryField::Description,
-                    ]
-                } else {
-                    vec![QueryField::Topic, QueryField::Title]
-                };
-                query.queries.push(Query {
-                    fields,
-                    query: s.to_owned(),
-                })
+        for term in &search.terms {
+            match term {
+                Term::DurationMin(duration_min) => query.duration_min = Some(*duration_min),
+                Term::DurationMax(duration_max) => query.duration_max = Some(*duration_max),
+                Term::Text(value) => text.push(value.as_str()),
+                Term::Not(term) => query.exclude.extend(term_query(term, &text_fields)),
+                Term::Or(terms) => query.any.push(
+                    terms
+                        .iter()
+                        .filter_map(|term| term_query(term, &text_fields))
+                        .collect(),
+                ),
+                term => query.queries.extend(term_query(term, &text_fields)),
             }
         }
 
+        if !text.is_empty() {
+            query.queries.push(Query {
+                fields: text_fields,
+                query: text.join(" "),
+            })
+        }
+
         query
     }
 }
 
+/// Convert a term that searches for a value into a query.
+///
+/// Returns `None` for durations, negations and `|` groups, which can't be
+/// nested in other terms.
+fn term_query(term: &Term, text_fields: &[QueryField]) -> Option<Query> {
+    let (fields, value) = match term {
+        Term::Channel(channel) => (vec![QueryField::Channel], channel),
+        Term::Topic(topic) => (vec![QueryField::Topic], topic),
+        Term::Title(title) => (vec![QueryField::Title], title),
+        Term::Description(description) => (vec![QueryField::Description], description),
+        Term::Text(text) => (text_fields.to_vec(), text),
+        _ => return None,
+    };
+    Some(Query {
+        fields,
+        query: value.clone(),
+    })
+}
+
 /// Request builder for the `/api/query` endpoint.
 #[derive(Debug)]
 pub struct MediathekQueryBuilder<'client> {
     client: &'client Mediathek,
     query: MediathekQuery,
+    limit: Option<usize>,
 }
 impl<'client> MediathekQueryBuilder<'client> {
     fn new(client: &'client Mediathek) -> Self {
         Self {
             client,
             query: MediathekQuery::default(),
+            limit: None,
         }
     }
 }
@@ -237,6 +578,70 @@ impl MediathekQueryBuilder<'_> {
         });
         self
     }
+    /// Exclude results that match a search query.
+    ///
+    /// The server doesn't support negation, so the results are filtered by
+    /// the client. Additional pages are retrieved to fill the requested page
+    /// size. See [`Query::matches`] for the matching semantics.
+    pub fn exclude(mut self, fields: impl Into<Vec<QueryField>>, query: impl Into<String>) -> Self {
+        self.query.exclude.push(Query::new(fields, query));
+        self
+    }
+    /// Add a group of search queries of which at least one must match.
+    ///
+    /// The server doesn't support `OR`, so a separate request is sent for
+    /// every combination of the alternatives of all groups. The results are
+    /// merged, de-duplicated by their [`id`](Item::id) and sorted according
+    /// to [`sort_by`](Self::sort_by). If the total count of results can't be
+    /// determined without retrieving all results, the reported
+    /// [`total_results`](crate::models::QueryInfo::total_results) is an upper
+    /// bound.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example(mediathek: mediathekviewweb::Mediathek) -> mediathekviewweb::Result<()> {
+    /// use mediathekviewweb::models::{Query, QueryField};
+    ///
+    /// let results = mediathek
+    ///     .query([QueryField::Topic], "tatort")
+    ///     .any([
+    ///         Query::new([QueryField::Channel], "ard"),
+    ///         Query::new([QueryField::Channel], "swr"),
+    ///     ])
+    ///     .exclude([QueryField::Title], "hörfassung")
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn any(mut self, queries: impl IntoIterator<Item = Query>) -> Self {
+        self.query.any.push(queries.into_iter().collect());
+        self
+    }
+    /// Only include results of a channel. Can be called several times to
+    /// include results of any of the channels.
+    ///
+    /// Unlike a query for [`QueryField::Channel`], this doesn't include
+    /// channels whose names contain the same words, e.g. `ZDF-tivi` for
+    /// [`Channel::Zdf`]. The results are filtered by the client and a separate
+    /// request is sent for every channel, like for [`any`](Self::any).
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example(mediathek: mediathekviewweb::Mediathek) -> mediathekviewweb::Result<()> {
+    /// use mediathekviewweb::models::{Channel, QueryField};
+    ///
+    /// let results = mediathek
+    ///     .query([QueryField::Topic], "heute")
+    ///     .channel(Channel::Zdf)
+    ///     .channel(Channel::ThreeSat)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn channel(mut self, channel: Channel) -> Self {
+        self.query.channels.push(channel);
+        self
+    }
     /// Filter for a minimum duration.
     pub fn duration_min(mut self, duration_min: impl Into<Duration>) -> Self {
         self.query.duration_min = Some(duration_min.into());
@@ -252,6 +657,45 @@ impl MediathekQueryBuilder<'_> {
         self.query.future = Some(include_future);
         self
     }
+    /// Only include media that are broadcast at or after `time`.
+    ///
+    /// The server doesn't support date filters, so the results are filtered
+    /// by the client. If no sort field is set, the results are sorted by
+    /// [`Timestamp`](SortField::Timestamp), so that no more requests are sent
+    /// once the results are past the window.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example(mediathek: mediathekviewweb::Mediathek) -> mediathekviewweb::Result<()> {
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// use futures_util::TryStreamExt;
+    /// use mediathekviewweb::models::QueryField;
+    ///
+    /// let last_week = mediathek
+    ///     .query([QueryField::Channel], "arte")
+    ///     .broadcast_after(SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60))
+    ///     .include_future(false)
+    ///     .size(50)
+    ///     .items()
+    ///     .try_collect::<Vec<_>>()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn broadcast_after(mut self, time: impl Into<SystemTime>) -> Self {
+        self.query.broadcast_after = Some(date::unix_timestamp(time.into()));
+        self.query.sort_by.get_or_insert(SortField::Timestamp);
+        self
+    }
+    /// Only include media that are broadcast before `time`.
+    ///
+    /// See [`broadcast_after`](Self::broadcast_after).
+    pub fn broadcast_before(mut self, time: impl Into<SystemTime>) -> Self {
+        self.query.broadcast_before = Some(date::unix_timestamp(time.into()));
+        self.query.sort_by.get_or_insert(SortField::Timestamp);
+        self
+    }
     /// Sort the results by a specific field.
     pub fn sort_by(mut self, sort_by: SortField) -> Self {
         self.query.sort_by = Some(sort_by);
@@ -276,30 +720,196 @@ impl MediathekQueryBuilder<'_> {
         self.query.offset = Some(offset);
         self
     }
+    /// Return the query that would be sent to the server.
+    pub fn into_query(self) -> MediathekQuery {
+        self.query
+    }
+    /// Limit the total count of results that are retrieved by
+    /// [`pages`](Self::pages) and [`items`](Self::items).
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
 }
-impl MediathekQueryBuilder<'_> {
+impl<'client> MediathekQueryBuilder<'client> {
     /// Build and send the request to the server.
     ///
     /// This call can be usually omitted since this type implements
     /// [`IntoFuture`].
     pub async fn send(self) -> crate::Result<QueryResult> {
-        self.client
-            .http
-            .post(format!(
-                "{base_url}/api/query",
-                base_url = self.client.base_url
-            ))
-            // https://github.com/mediathekview/mediathekviewweb/issues/145#issuecomment-555054562
-            .header(reqwest::header::CONTENT_TYPE, "text/plain")
-            .json(&self.query)
-            .send()
-            .await?
-            .error_for_status()?
-            .json::<ApiResult<QueryResult>>()
-            .await?
-            .into()
+        self.client.send_query(&self.query).await
     }
+
+    /// Send requests to the server until all results have been retrieved,
+    /// yielding one page of results at a time.
+    ///
+    /// The page size can be set using [`size`](Self::size) and the first page
+    /// starts at [`offset`](Self::offset). The stream ends when all of the
+    /// server's [`total_results`](crate::models::QueryInfo::total_results)
+    /// or the [`limit`](Self::limit) have been retrieved.
+    ///
+    /// Queries with filters that are applied by the client, e.g.
+    /// [`exclude`](Self::exclude) or
+    /// [`broadcast_after`](Self::broadcast_after), are paginated like
+    /// [`send`](Self::send) would return them: the offset skips filtered
+    /// results and every page but the last contains as many filtered results
+    /// as the page size. Queries that are sent as several queries, e.g.
+    /// because of [`any`](Self::any) groups, keep the offset of each of them
+    /// across pages, so every result is only requested once.
+    ///
+    /// If a request fails, the error is yielded and the stream ends.
+    pub fn pages(self) -> impl Stream<Item = crate::Result<QueryResult>> + Send + 'client {
+        let state = (self.client, Pagination::new(self.query, self.limit));
+
+        futures_util::stream::unfold(state, |(client, mut pagination)| async move {
+            loop {
+                if let Some(page) = pagination.next_page() {
+                    return Some((Ok(page), (client, pagination)));
+                }
+                let result = client.send_query(pagination.next_query()?).await;
+                if let Some(page) = pagination.process(result) {
+                    return Some((page, (client, pagination)));
+                }
+            }
+        })
+    }
+
+    /// Send requests to the server until all results have been retrieved,
+    /// yielding one item at a time.
+    ///
+    /// See [`pages`](Self::pages) for details on pagination and error handling.
+    pub fn items(self) -> impl Stream<Item = crate::Result<Item>> + Send + 'client {
+        self.pages().flat_map(|page| {
+            futures_util::stream::iter(match page {
+                Ok(page) => page.results.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            })
+        })
+    }
+}
+/// Build a request for the `/api/query` endpoint.
+fn query_request(query: &MediathekQuery) -> crate::Result<Request> {
+    Ok(Request {
+        method: Method::Post,
+        path: "/api/query".into(),
+        // https://github.com/mediathekview/mediathekviewweb/issues/145#issuecomment-555054562
+        headers: vec![("Content-Type".into(), "text/plain".into())],
+        body: serde_json::to_vec(query)?,
+    })
+}
+
+/// Parse the response of the `/api/query` endpoint.
+fn query_response(response: Response) -> crate::Result<QueryResult> {
+    match serde_json::from_slice::<ApiResult<QueryResult>>(&response.body) {
+        Ok(result) if response.is_success() => result.into(),
+        Err(e) if response.is_success() => Err(e.into()),
+        // prefer the error message provided by the server
+        Ok(result @ ApiResult { err: Some(_), .. }) => result.into(),
+        _ => Err(Error::Status(response.status)),
+    }
+}
+
+/// Calculate the 64-bit FNV-1a hash of some bytes.
+///
+/// Unlike [`std::hash::DefaultHasher`], the result is stable and can be
+/// persisted.
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The state of a paginated query.
+#[derive(Debug)]
+struct Pagination {
+    /// The query that is sent for each page.
+    query: MediathekQuery,
+    /// The merged and filtered pages of a query that
+    /// [needs emulation](MediathekQuery::needs_emulation).
+    merge: Option<Merge>,
+    remaining: Option<usize>,
+    done: bool,
 }
+impl Pagination {
+    fn new(query: MediathekQuery, limit: Option<usize>) -> Self {
+        let merge = query.needs_emulation().then(|| Merge::new(&query));
+        Self {
+            query,
+            merge,
+            remaining: limit,
+            done: false,
+        }
+    }
+
+    /// Return the query for the next page or `None` if all pages have been
+    /// retrieved.
+    fn next_query(&mut self) -> Option<&MediathekQuery> {
+        if self.done || self.remaining == Some(0) {
+            return None;
+        }
+        if let Some(merge) = &self.merge {
+            return merge.next_query();
+        }
+        if let (Some(size), Some(remaining)) = (self.query.size, self.remaining) {
+            self.query.size = Some(size.min(remaining));
+        }
+        Some(&self.query)
+    }
+
+    /// Process the response to the query returned by
+    /// [`next_query`](Self::next_query) and return the page that should be
+    /// yielded, if any.
+    fn process(
+        &mut self,
+        result: crate::Result<QueryResult>,
+    ) -> Option<crate::Result<QueryResult>> {
+        let mut page = match result {
+            Ok(page) => page,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        if let Some(merge) = &mut self.merge {
+            merge.process(page);
+            return None;
+        }
+        if page.results.is_empty() {
+            self.done = true;
+            return None;
+        }
+
+        let offset = self.query.offset.unwrap_or(0) + page.results.len();
+        self.query.offset = Some(offset);
+        self.done = offset as u64 >= page.query_info.total_results;
+
+        self.limit(&mut page);
+        Some(Ok(page))
+    }
+
+    /// Return the next page if it can be merged from the results that have
+    /// already been retrieved.
+    ///
+    /// This must be checked before requesting the [`next_query`](Self::next_query).
+    fn next_page(&mut self) -> Option<QueryResult> {
+        if self.done || self.remaining == Some(0) {
+            return None;
+        }
+        let mut page = self.merge.as_mut()?.next_page()?;
+        self.limit(&mut page);
+        Some(page)
+    }
+
+    /// Remove the results of a page that exceed the limit.
+    fn limit(&mut self, page: &mut QueryResult) {
+        if let Some(remaining) = &mut self.remaining {
+            page.results.truncate(*remaining);
+            page.query_info.result_count = page.results.len();
+            *remaining -= page.results.len();
+        }
+    }
+}
+
 impl<'client> IntoFuture for MediathekQueryBuilder<'client> {
     type Output = crate::Result<QueryResult>;
     type IntoFuture = Pin<Box<dyn Future<Output = Self::Output> + Send + 'client>>;
@@ -311,48 +921,112 @@ impl<'client> IntoFuture for MediathekQueryBuilder<'client> {
 
 #[cfg(test)]
 mod tests {
-    use std::time::Duration;
+    use std::time::{Duration, UNIX_EPOCH};
 
+    use futures_util::StreamExt;
+
+    #[cfg(feature = "reqwest")]
+    use crate::{
+        cache::{CacheStats, MemoryCache, QueryCache},
+        mock::MockServer,
+        retry::RetryPolicy,
+        Error,
+    };
     use crate::{
-        models::{Query, QueryField},
-        Mediathek, MediathekQuery,
+        local::LocalMediathek,
+        models::{
+            ApiResult, Channel, Item, MediathekQuery, Query, QueryField, SortField, SortOrder,
+        },
+        retry::RateLimiter,
+        search_string::{ParseError, ParseErrorKind},
+        transport::{BoxFuture, Method, Request, Response, Transport, TransportError},
+        Mediathek,
     };
 
+    /// Answers queries using a [`LocalMediathek`].
+    #[derive(Debug)]
+    struct LocalTransport(LocalMediathek);
+    impl Transport for LocalTransport {
+        fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, TransportError>> {
+            Box::pin(async move {
+                if request.method != Method::Post || request.path != "/api/query" {
+                    return Ok(Response {
+                        status: 404,
+                        headers: Vec::new(),
+                        body: Vec::new(),
+                    });
+                }
+                let result = self.0.execute(&serde_json::from_slice(&request.body)?);
+                Ok(Response {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: serde_json::to_vec(&ApiResult {
+                        err: None,
+                        result: Some(result),
+                    })?,
+                })
+            })
+        }
+    }
+
+    fn local_mediathek() -> Mediathek {
+        Mediathek::with_transport(LocalTransport(LocalMediathek::new(
+            (0..25)
+                .map(|i| Item {
+                    channel: "ARD".into(),
+                    topic: "tagesschau".into(),
+                    title: format!("tagesschau {i}"),
+                    description: None,
+                    timestamp: 1696269600 - i * 86400,
+                    duration: (i % 5 != 0).then(|| Duration::from_secs(15 * 60)),
+                    size: None,
+                    url_website: String::new(),
+                    url_subtitle: None,
+                    url_video: String::new(),
+                    url_video_low: None,
+                    url_video_hd: None,
+                    filmliste_timestamp: 1696361700,
+                    id: i.to_string(),
+                })
+                .collect::<Vec<_>>(),
+        )))
+    }
+
     #[test]
     fn test_search_string() {
         assert_eq!(
             MediathekQuery::from_search_string("!ard", false),
-            MediathekQuery {
+            Ok(MediathekQuery {
                 queries: vec![Query {
                     fields: vec![QueryField::Channel],
                     query: "ard".into()
                 }],
                 ..Default::default()
-            }
+            })
         );
         assert_eq!(
             MediathekQuery::from_search_string("+gebärdensprache", false),
-            MediathekQuery {
+            Ok(MediathekQuery {
                 queries: vec![Query {
                     fields: vec![QueryField::Title],
                     query: "gebärdensprache".into()
                 }],
                 ..Default::default()
-            }
+            })
         );
         assert_eq!(
             MediathekQuery::from_search_string("*norwegen", false),
-            MediathekQuery {
+            Ok(MediathekQuery {
                 queries: vec![Query {
                     fields: vec![QueryField::Description],
                     query: "norwegen".into()
                 }],
                 ..Default::default()
-            }
+            })
         );
         assert_eq!(
             MediathekQuery::from_search_string("!ard #wetter", false),
-            MediathekQuery {
+            Ok(MediathekQuery {
                 queries: vec![
                     Query {
                         fields: vec![QueryField::Channel],
@@ -364,28 +1038,39 @@ mod tests {
                     }
                 ],
                 ..Default::default()
-            }
+            })
+        );
+        // commas join the words of a phrase, as used in the examples
+        assert_eq!(
+            MediathekQuery::from_search_string("#sendung,mit,der,maus", false),
+            Ok(MediathekQuery {
+                queries: vec![Query {
+                    fields: vec![QueryField::Topic],
+                    query: "sendung mit der maus".into()
+                }],
+                ..Default::default()
+            })
         );
         assert_eq!(
             MediathekQuery::from_search_string(">60", false),
-            MediathekQuery {
+            Ok(MediathekQuery {
                 duration_min: Some(Duration::from_secs(60 * 60)),
                 ..Default::default()
-            }
+            })
         );
         assert_eq!(
             MediathekQuery::from_search_string("*diane,kruger", false),
-            MediathekQuery {
+            Ok(MediathekQuery {
                 queries: vec![Query {
                     fields: vec![QueryField::Description],
                     query: "diane kruger".into()
                 }],
                 ..Default::default()
-            }
+            })
         );
         assert_eq!(
             MediathekQuery::from_search_string("!ard !ndr #sturm,der,liebe #rote,rosen", false),
-            MediathekQuery {
+            Ok(MediathekQuery {
                 queries: vec![
                     Query {
                         fields: vec![QueryField::Channel],
@@ -405,11 +1090,11 @@ mod tests {
                     }
                 ],
                 ..Default::default()
-            }
+            })
         );
         assert_eq!(
             MediathekQuery::from_search_string("!ard !ndr #sturm,der,liebe #rote,rosen", false),
-            MediathekQuery {
+            Ok(MediathekQuery {
                 queries: vec![
                     Query {
                         fields: vec![QueryField::Channel],
@@ -429,23 +1114,23 @@ mod tests {
                     }
                 ],
                 ..Default::default()
-            }
+            })
         );
 
         assert_eq!(
             MediathekQuery::from_search_string("test", false),
-            MediathekQuery {
+            Ok(MediathekQuery {
                 queries: vec![Query {
                     fields: vec![QueryField::Topic, QueryField::Title],
                     query: "test".into()
                 },],
                 ..Default::default()
-            }
+            })
         );
 
         assert_eq!(
             MediathekQuery::from_search_string("test", true),
-            MediathekQuery {
+            Ok(MediathekQuery {
                 queries: vec![Query {
                     fields: vec![
                         QueryField::Channel,
@@ -456,36 +1141,571 @@ mod tests {
                     query: "test".into()
                 },],
                 ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            MediathekQuery::from_search_string("!ard foo \"bar baz\" #wetter", false),
+            Ok(MediathekQuery {
+                queries: vec![
+                    Query {
+                        fields: vec![QueryField::Channel],
+                        query: "ard".into()
+                    },
+                    Query {
+                        fields: vec![QueryField::Topic],
+                        query: "wetter".into()
+                    },
+                    Query {
+                        fields: vec![QueryField::Topic, QueryField::Title],
+                        query: "foo bar baz".into()
+                    }
+                ],
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            MediathekQuery::from_search_string("!ard | !zdf foo -#wetter -bar a|b", false),
+            Ok(MediathekQuery {
+                queries: vec![Query::new([QueryField::Topic, QueryField::Title], "foo")],
+                exclude: vec![
+                    Query::new([QueryField::Topic], "wetter"),
+                    Query::new([QueryField::Topic, QueryField::Title], "bar")
+                ],
+                any: vec![
+                    vec![
+                        Query::new([QueryField::Channel], "ard"),
+                        Query::new([QueryField::Channel], "zdf")
+                    ],
+                    vec![
+                        Query::new([QueryField::Topic, QueryField::Title], "a"),
+                        Query::new([QueryField::Topic, QueryField::Title], "b")
+                    ]
+                ],
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            MediathekQuery::from_search_string(">abc", false),
+            Err(ParseError {
+                kind: ParseErrorKind::InvalidDuration,
+                span: 1..4
+            })
+        );
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_items() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start()?;
+        let mediathek = Mediathek::new_with_url(server.url(), "test".parse().unwrap())?;
+
+        let items = mediathek
+            .execute(MediathekQuery::default())
+            .size(3)
+            .limit(7)
+            .items()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(items.len(), 7);
+
+        let pages = mediathek
+            .execute(MediathekQuery::default())
+            .size(3)
+            .limit(7)
+            .pages()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            pages
+                .iter()
+                .map(|page| page.results.len())
+                .collect::<Vec<_>>(),
+            [3, 3, 1]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transport() -> Result<(), Box<dyn std::error::Error>> {
+        let mediathek = local_mediathek();
+
+        let result = mediathek
+            .query([QueryField::Title], "tagesschau")
+            .size(3)
+            .offset(1)
+            .await?;
+        assert_eq!(result.query_info.total_results, 25);
+        assert_eq!(
+            result
+                .results
+                .iter()
+                .map(|item| item.id.as_str())
+                .collect::<Vec<_>>(),
+            ["1", "2", "3"]
+        );
+
+        let items = mediathek
+            .query([QueryField::Title], "tagesschau")
+            .size(10)
+            .items()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(items.len(), 25);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_emulation() -> Result<(), Box<dyn std::error::Error>> {
+        let local = LocalMediathek::new(crate::mock::fixtures());
+        let mediathek = Mediathek::with_transport(LocalTransport(local.clone()));
+
+        for (search, sort_by) in [
+            ("!ard | !zdf -livestream", Some(SortField::Duration)),
+            (
+                "tagesschau | journal | grad -12:00",
+                Some(SortField::Timestamp),
+            ),
+            (
+                "!ard|!zdf #tagesschau|!3sat -gebärdensprache",
+                Some(SortField::Duration),
+            ),
+            ("-#tagesschau -!zdf", None),
+        ] {
+            for (size, offset) in [(3, 0), (2, 3), (10, 0)] {
+                let query = MediathekQuery {
+                    sort_by,
+                    size: Some(size),
+                    offset: Some(offset),
+                    ..MediathekQuery::from_search_string(search, false)?
+                };
+                let expected = local.execute(&query);
+                let result = mediathek.execute(query).await?;
+                assert_eq!(result.results, expected.results, "{search} ({offset})");
+                assert!(result.query_info.total_results >= expected.query_info.total_results);
+            }
+        }
+
+        for (search, sort_order) in [
+            ("!ard | !zdf | !3sat", SortOrder::Descending),
+            ("-livestream", SortOrder::Ascending),
+        ] {
+            for (size, offset) in [(1, 0), (2, 1), (10, 0)] {
+                let query = mediathek
+                    .query_string(search, false)?
+                    .broadcast_after(UNIX_EPOCH + Duration::from_secs(1696183200))
+                    .broadcast_before(UNIX_EPOCH + Duration::from_secs(1696276800))
+                    .sort_order(sort_order)
+                    .size(size)
+                    .offset(offset)
+                    .into_query();
+                let expected = local.execute(&query);
+                let result = mediathek.execute(query).await?;
+                assert_eq!(result.results, expected.results, "{search} ({offset})");
             }
+        }
+
+        // the total count is exact if all results have been retrieved
+        let result = mediathek
+            .query([QueryField::Channel], "ard")
+            .exclude([QueryField::Topic], "tagesschau")
+            .size(10)
+            .await?;
+        assert_eq!(result.query_info.total_results, 2);
+        assert_eq!(result.query_info.filmliste_timestamp, 1696339020);
+
+        let query = mediathek
+            .execute(MediathekQuery::default())
+            .any([
+                Query::new([QueryField::Channel], "zdf"),
+                Query::new([QueryField::Topic], "livestream"),
+            ])
+            .size(1)
+            .into_query();
+        assert_eq!(query.any.len(), 1);
+        let items = mediathek
+            .execute(query)
+            .items()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| item.title.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "ZDF Livestream",
+                "heute journal vom 2. Oktober 2023",
+                "Das Erste Livestream"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_channel_filter() -> Result<(), Box<dyn std::error::Error>> {
+        let mut items = crate::mock::fixtures();
+        let mut tivi = items[7].clone();
+        tivi.channel = Channel::ZdfTivi;
+        tivi.id = "tivi".into();
+        items.push(tivi);
+        let local = LocalMediathek::new(items);
+        let mediathek = Mediathek::with_transport(LocalTransport(local.clone()));
+
+        let query = mediathek
+            .execute(MediathekQuery::default())
+            .channel(Channel::Zdf)
+            .into_query();
+        // the server query for ZDF also returns ZDF-tivi
+        let server_query = &query.server_queries()[0];
+        assert_eq!(local.execute(server_query).results.len(), 3);
+        let items = mediathek
+            .execute(query)
+            .size(1)
+            .items()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(|item| item.channel == Channel::Zdf));
+
+        for (size, offset) in [(2, 0), (3, 2), (10, 0)] {
+            let query = mediathek
+                .execute(MediathekQuery::default())
+                .channel(Channel::ArteDe)
+                .channel(Channel::ThreeSat)
+                .channel(Channel::ZdfTivi)
+                .sort_by(SortField::Timestamp)
+                .size(size)
+                .offset(offset)
+                .into_query();
+            let expected = local.execute(&query);
+            let result = mediathek.execute(query).await?;
+            assert_eq!(result.results, expected.results, "{offset}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() -> Result<(), Box<dyn std::error::Error>> {
+        let mediathek =
+            local_mediathek().with_rate_limit(RateLimiter::new(Duration::from_millis(50)));
+
+        let start = std::time::Instant::now();
+        let pages = mediathek
+            .query([QueryField::Title], "tagesschau")
+            .size(10)
+            .pages()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(pages.len(), 3);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        Ok(())
+    }
+
+    /// Pages of queries with client-side filters match the results of
+    /// single requests.
+    #[tokio::test]
+    async fn test_filtered_pages() -> Result<(), Box<dyn std::error::Error>> {
+        let item = crate::mock::fixtures().remove(0);
+        let items = (0..10)
+            .map(|i| Item {
+                title: if i % 2 == 0 {
+                    format!("x {i}")
+                } else {
+                    i.to_string()
+                },
+                id: i.to_string(),
+                ..item.clone()
+            })
+            .collect::<Vec<_>>();
+        let mediathek = Mediathek::with_transport(LocalTransport(LocalMediathek::new(items)));
+        let query = |size, offset| {
+            mediathek
+                .execute(MediathekQuery::default())
+                .exclude([QueryField::Title], "x")
+                .size(size)
+                .offset(offset)
+        };
+
+        let result = query(3, 2).send().await?;
+        assert_eq!(
+            result
+                .results
+                .iter()
+                .map(|item| item.id.as_str())
+                .collect::<Vec<_>>(),
+            ["5", "7", "9"]
+        );
+        for (size, offset) in [(3, 2), (2, 1), (1, 0), (10, 4)] {
+            let expected = query(size, offset).size(100).send().await?.results;
+            let pages = query(size, offset)
+                .pages()
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()?;
+            assert!(pages[..pages.len() - 1]
+                .iter()
+                .all(|page| page.results.len() == size));
+            assert_eq!(
+                pages
+                    .into_iter()
+                    .flat_map(|page| page.results)
+                    .collect::<Vec<_>>(),
+                expected,
+                "{size} ({offset})"
+            );
+            let items = query(size, offset)
+                .items()
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(items, expected, "{size} ({offset})");
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_broadcast_window() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start()?;
+        let mediathek = Mediathek::new_with_url(server.url(), "test".parse().unwrap())?;
+        let query = mediathek
+            .execute(MediathekQuery::default())
+            .broadcast_after(UNIX_EPOCH + Duration::from_secs(1696183200))
+            .broadcast_before(UNIX_EPOCH + Duration::from_secs(1696270000))
+            .size(3);
+
+        let pages = query
+            .pages()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            pages
+                .iter()
+                .map(|page| page
+                    .results
+                    .iter()
+                    .map(|item| item.timestamp)
+                    .collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            [vec![1696269600, 1696240800, 1696185467], vec![1696183200]]
+        );
+        // the last page is never requested because the results are sorted
+        assert_eq!(server.request_count(), 3);
+
+        let result = mediathek
+            .execute(MediathekQuery::default())
+            .broadcast_after(UNIX_EPOCH + Duration::from_secs(1696183200))
+            .broadcast_before(UNIX_EPOCH + Duration::from_secs(1696270000))
+            .size(2)
+            .offset(1)
+            .await?;
+        assert_eq!(result.results.len(), 2);
+        assert_eq!(result.results[0].timestamp, 1696240800);
+        assert_eq!(result.query_info.total_results, 4);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_merged_pages() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start()?;
+        let mediathek = Mediathek::new_with_url(server.url(), "test".parse().unwrap())?;
+        let query = mediathek
+            .execute(MediathekQuery::default())
+            .any([
+                Query::new([QueryField::Channel], "ard"),
+                Query::new([QueryField::Topic], "livestream"),
+            ])
+            .sort_by(SortField::Timestamp)
+            .into_query();
+        let expected = mediathek.execute(query.clone()).size(100).await?;
+        assert_eq!(expected.results.len(), 7);
+        let requests = server.request_count();
+
+        let pages = mediathek
+            .execute(query.clone())
+            .size(2)
+            .pages()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            pages
+                .iter()
+                .map(|page| page.results.len())
+                .collect::<Vec<_>>(),
+            [2, 2, 2, 1]
         );
+        assert_eq!(
+            pages
+                .into_iter()
+                .flat_map(|page| page.results)
+                .collect::<Vec<_>>(),
+            expected.results
+        );
+        // every result is only requested once
+        assert_eq!(server.request_count() - requests, 4);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_retry() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start()?;
+        let mediathek = Mediathek::new_with_url(server.url(), "test".parse().unwrap())?
+            .with_retry_policy(
+                RetryPolicy::new()
+                    .max_attempts(3)
+                    .initial_backoff(Duration::from_millis(10)),
+            );
+
+        server.fail_next(429, Some(0));
+        server.fail_next(502, None);
+        let result = mediathek.query([QueryField::Topic], "livestream").await?;
+        assert_eq!(result.results.len(), 2);
+        assert_eq!(server.request_count(), 3);
+
+        // client errors are not retried
+        server.fail_next(400, None);
+        assert!(matches!(
+            mediathek.query([QueryField::Topic], "livestream").await,
+            Err(Error::Status(400))
+        ));
+        assert_eq!(server.request_count(), 4);
+
+        Ok(())
     }
 
+    #[cfg(feature = "reqwest")]
     #[tokio::test]
-    async fn test_query() -> Result<(), Box<dyn std::error::Error>> {
-        let mediathek = Mediathek::new(
-            format!(
-                "{} Test Suite ({})",
-                env!("CARGO_PKG_NAME"),
-                env!("CARGO_PKG_REPOSITORY")
+    async fn test_builder() -> Result<(), Box<dyn std::error::Error>> {
+        for url in [
+            "mediathekviewweb.de",
+            "ftp://localhost",
+            "http://localhost/?q=1",
+        ] {
+            assert!(matches!(
+                Mediathek::builder().base_url(url).build(),
+                Err(Error::InvalidBaseUrl(invalid)) if invalid == url
+            ));
+        }
+
+        let server = MockServer::start()?;
+        let mediathek = Mediathek::builder()
+            .base_url(format!("{}/", server.url()))
+            .user_agent("test".parse().unwrap())
+            .header(
+                reqwest::header::ACCEPT_LANGUAGE,
+                reqwest::header::HeaderValue::from_static("de"),
             )
-            .parse()
-            .unwrap(),
-        )?;
+            .timeout(Duration::from_secs(10))
+            .connect_timeout(Duration::from_secs(5))
+            .build()?;
+        let result = mediathek.query([QueryField::Topic], "livestream").await?;
+        assert_eq!(result.results.len(), 2);
 
-        mediathek.query([QueryField::Topic], "tagesschau").await?;
+        let mediathek = Mediathek::builder()
+            .base_url(server.url())
+            .client(reqwest::Client::new())
+            .build()?;
+        let result = mediathek.query([QueryField::Topic], "livestream").await?;
+        assert_eq!(result.results.len(), 2);
 
-        // livestreams return `""` as the duration
+        Ok(())
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_cache() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start()?;
+        let mediathek = Mediathek::builder()
+            .base_url(server.url())
+            .cache(QueryCache::new(MemoryCache::new(10)))
+            .build()?;
+
+        let first = mediathek.query([QueryField::Topic], "tagesschau").await?;
+        let second = mediathek.query([QueryField::Topic], "tagesschau").await?;
+        assert_eq!(first, second);
         mediathek.query([QueryField::Topic], "livestream").await?;
 
-        assert!(mediathek
-            .query_string("<60 >50", false)
-            .size(5)
-            .await?
+        assert_eq!(server.request_count(), 2);
+        assert_eq!(
+            mediathek.cache_stats(),
+            Some(CacheStats { hits: 1, misses: 2 })
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_mock() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start()?;
+        let mediathek = Mediathek::new_with_url(server.url(), "test".parse().unwrap())?;
+
+        let result = mediathek
+            .query_string("#tagesschau >12", false)?
+            .sort_by(SortField::Timestamp)
+            .sort_order(SortOrder::Ascending)
+            .await?;
+        assert_eq!(result.query_info.filmliste_timestamp, 1696339020);
+        assert_eq!(result.query_info.total_results, 3);
+        assert_eq!(
+            result
+                .results
+                .iter()
+                .map(|item| item.timestamp)
+                .collect::<Vec<_>>(),
+            [1696183200, 1696185467, 1696269600]
+        );
+
+        // livestreams return `""` as the duration
+        let result = mediathek.query([QueryField::Topic], "livestream").await?;
+        assert_eq!(result.results.len(), 2);
+        assert!(result.results.iter().all(|item| item.duration.is_none()
+            && item.size.is_none()
+            && item.description.is_none()));
+
+        let result = mediathek
+            .query([QueryField::Channel], "arte")
+            .include_future(false)
+            .await?;
+        assert!(result.results.is_empty());
+
+        let result = mediathek.query_string(">15 <16", false)?.await?;
+        assert_eq!(result.results.len(), 2);
+        assert!(result
             .results
             .into_iter()
-            .flat_map(|show| show.duration)
+            .flat_map(|item| item.duration)
             .all(|duration| {
-                duration >= Duration::from_secs(50 * 60) && duration <= Duration::from_secs(60 * 60)
+                duration >= Duration::from_secs(15 * 60) && duration <= Duration::from_secs(16 * 60)
             }));
 
         Ok(())
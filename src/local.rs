@@ -0,0 +1,444 @@
+//! Execute [`MediathekQuery`]s against a local collection of [`Item`]s without
+//! a server, e.g. items loaded from a [Filmliste](crate::filmliste).
+//!
+//! The matching semantics mimic the MediathekViewWeb server:
+//!
+//! - Texts are split into words at every character that is not alphanumeric.
+//!   Words are compared case-insensitively and with common diacritics removed
+//!   (e.g. `Ä` matches `a` and `ß` matches `ss`).
+//! - Every word of a [`Query`] must be found in at least one of its fields. A
+//!   word is found if a word in the field starts with it, so `tagessch` finds
+//!   `Tagesschau`.
+//! - All queries of a [`MediathekQuery`] must match, none of its
+//!   [`exclude`](MediathekQuery::exclude)d queries may match and at least one
+//!   query of every [`any`](MediathekQuery::any) group must match.
+//! - If [`channels`](MediathekQuery::channels) are set, the item's channel
+//!   must be one of them exactly.
+//! - Items without a duration (e.g. livestreams) never match if a minimum or
+//!   maximum duration is set.
+//! - Media with a broadcasting date in the future are excluded if
+//!   [`future`](MediathekQuery::future) is `Some(false)`.
+//! - [`broadcast_after`](MediathekQuery::broadcast_after) is inclusive and
+//!   [`broadcast_before`](MediathekQuery::broadcast_before) is exclusive.
+//! - If [`sort_by`](MediathekQuery::sort_by) is not set, the original order of
+//!   the items is kept. The sort order defaults to descending.
+//! - At most [`DEFAULT_SIZE`] items are returned if
+//!   [`size`](MediathekQuery::size) is not set.
+//!
+//! # Example
+//! ```rust
+//! use mediathekviewweb::{
+//!     local::LocalMediathek,
+//!     models::{MediathekQuery, SortField},
+//! };
+//!
+//! let mediathek = LocalMediathek::new(Vec::new());
+//! let results = mediathek.execute(&MediathekQuery {
+//!     sort_by: Some(SortField::Timestamp),
+//!     ..MediathekQuery::from_search_string("!ard #tagesschau", false).unwrap()
+//! });
+//! assert_eq!(results.query_info.total_results, 0);
+//! ```
+
+use std::{
+    cmp::Ordering,
+    io::BufRead,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    filmliste::FilmlisteReader,
+    models::{
+        Item, MediathekQuery, Query, QueryField, QueryInfo, QueryResult, SortField, SortOrder,
+    },
+};
+
+/// The count of results that are returned if no size is specified.
+pub const DEFAULT_SIZE: usize = 10;
+
+/// An in-memory collection of [`Item`]s that can be queried like a
+/// MediathekViewWeb server.
+#[derive(Clone, Debug, Default)]
+pub struct LocalMediathek {
+    items: Vec<Item>,
+    filmliste_timestamp: i64,
+}
+impl LocalMediathek {
+    /// Create a new collection from a list of items.
+    ///
+    /// The reported Filmliste timestamp is the latest timestamp of all items.
+    pub fn new(items: impl Into<Vec<Item>>) -> Self {
+        let items = items.into();
+        let filmliste_timestamp = items
+            .iter()
+            .map(|item| item.filmliste_timestamp)
+            .max()
+            .unwrap_or_default();
+        Self {
+            items,
+            filmliste_timestamp,
+        }
+    }
+
+    /// Create a new collection by reading all items of a Filmliste.
+    pub fn from_filmliste<R: BufRead>(reader: FilmlisteReader<R>) -> crate::Result<Self> {
+        let filmliste_timestamp = reader.header().timestamp;
+        Ok(Self {
+            items: reader.collect::<crate::Result<_>>()?,
+            filmliste_timestamp,
+        })
+    }
+
+    /// All items of this collection.
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    /// Execute a query against the items of this collection.
+    pub fn execute(&self, query: &MediathekQuery) -> QueryResult {
+        let start = Instant::now();
+        let now = unix_now();
+
+        let mut results = self
+            .items
+            .iter()
+            .filter(|item| query.matches_at(item, now))
+            .collect::<Vec<_>>();
+
+        if let Some(sort_by) = query.sort_by {
+            results.sort_by(|a, b| {
+                let ordering = compare(sort_by, a, b);
+                match query.sort_order.unwrap_or(SortOrder::Descending) {
+                    SortOrder::Ascending => ordering,
+                    SortOrder::Descending => ordering.reverse(),
+                }
+            });
+        }
+
+        let total_results = results.len() as u64;
+        let results = results
+            .into_iter()
+            .skip(query.offset.unwrap_or(0))
+            .take(query.size.unwrap_or(DEFAULT_SIZE))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        QueryResult {
+            query_info: QueryInfo {
+                filmliste_timestamp: self.filmliste_timestamp,
+                result_count: results.len(),
+                search_engine_time: start.elapsed(),
+                total_results,
+            },
+            results,
+        }
+    }
+}
+impl FromIterator<Item> for LocalMediathek {
+    fn from_iter<T: IntoIterator<Item = Item>>(iter: T) -> Self {
+        Self::new(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl MediathekQuery {
+    /// Check whether an item matches all queries and filters of this query.
+    ///
+    /// Sorting and pagination are ignored. See the [module documentation](self)
+    /// for details on the matching semantics.
+    pub fn matches(&self, item: &Item) -> bool {
+        self.matches_at(item, unix_now())
+    }
+
+    fn matches_at(&self, item: &Item, now: i64) -> bool {
+        if self.duration_min.is_some() || self.duration_max.is_some() {
+            let Some(duration) = item.duration else {
+                return false;
+            };
+            if self.duration_min.is_some_and(|min| duration < min)
+                || self.duration_max.is_some_and(|max| duration > max)
+            {
+                return false;
+            }
+        }
+        if self.future == Some(false) && item.timestamp > now {
+            return false;
+        }
+        if !self.in_broadcast_window(item) || !self.has_channel(item) {
+            return false;
+        }
+        self.queries.iter().all(|query| query.matches(item))
+            && !self.exclude.iter().any(|query| query.matches(item))
+            && self
+                .any
+                .iter()
+                .all(|group| group.iter().any(|query| query.matches(item)))
+    }
+
+    /// Check whether an item was broadcast inside the window set by
+    /// [`broadcast_after`](Self::broadcast_after) and
+    /// [`broadcast_before`](Self::broadcast_before).
+    pub(crate) fn in_broadcast_window(&self, item: &Item) -> bool {
+        self.broadcast_after
+            .is_none_or(|after| item.timestamp >= after)
+            && self
+                .broadcast_before
+                .is_none_or(|before| item.timestamp < before)
+    }
+
+    /// Check whether an item is of one of the [`channels`](Self::channels),
+    /// if any are set.
+    pub(crate) fn has_channel(&self, item: &Item) -> bool {
+        self.channels.is_empty() || self.channels.contains(&item.channel)
+    }
+}
+
+impl Query {
+    /// Check whether all words of this query are found in the item's fields.
+    ///
+    /// See the [module documentation](self) for details on the matching
+    /// semantics.
+    pub fn matches(&self, item: &Item) -> bool {
+        let item_words = self
+            .fields
+            .iter()
+            .flat_map(|field| match field {
+                QueryField::Topic => words(&item.topic),
+                QueryField::Title => words(&item.title),
+                QueryField::Description => words(item.description.as_deref().unwrap_or_default()),
+                QueryField::Channel => words(item.channel.as_str()),
+            })
+            .collect::<Vec<_>>();
+
+        words(&self.query)
+            .all(|query_word| item_words.iter().any(|word| word.starts_with(&query_word)))
+    }
+}
+
+pub(crate) fn compare(sort_by: SortField, a: &Item, b: &Item) -> Ordering {
+    match sort_by {
+        SortField::Channel => a.channel.as_str().cmp(b.channel.as_str()),
+        SortField::Timestamp => a.timestamp.cmp(&b.timestamp),
+        SortField::Duration => a.duration.cmp(&b.duration),
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+/// Splits a text into normalized words.
+fn words(s: &str) -> impl Iterator<Item = String> + '_ {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(normalize)
+}
+
+/// Converts a word to lowercase and removes common diacritics.
+fn normalize(word: &str) -> String {
+    let mut normalized = String::with_capacity(word.len());
+    for c in word.chars().flat_map(char::to_lowercase) {
+        match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => normalized.push('a'),
+            'æ' => normalized.push_str("ae"),
+            'ç' => normalized.push('c'),
+            'è' | 'é' | 'ê' | 'ë' => normalized.push('e'),
+            'ì' | 'í' | 'î' | 'ï' => normalized.push('i'),
+            'ñ' => normalized.push('n'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => normalized.push('o'),
+            'œ' => normalized.push_str("oe"),
+            'ß' => normalized.push_str("ss"),
+            'ù' | 'ú' | 'û' | 'ü' => normalized.push('u'),
+            'ý' | 'ÿ' => normalized.push('y'),
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::LocalMediathek;
+    use crate::models::{Channel, Item, MediathekQuery, Query, QueryField, SortField, SortOrder};
+
+    fn item(channel: &str, topic: &str, title: &str, timestamp: i64, minutes: Option<u64>) -> Item {
+        Item {
+            channel: channel.into(),
+            topic: topic.into(),
+            title: title.into(),
+            description: Some(format!("{topic}: {title}")),
+            timestamp,
+            duration: minutes.map(|minutes| Duration::from_secs(minutes * 60)),
+            size: None,
+            url_website: String::new(),
+            url_subtitle: None,
+            url_video: String::new(),
+            url_video_low: None,
+            url_video_hd: None,
+            filmliste_timestamp: 1698083280,
+            id: format!("{channel}-{timestamp}"),
+        }
+    }
+
+    fn mediathek() -> LocalMediathek {
+        LocalMediathek::new([
+            item(
+                "ARD",
+                "tagesschau",
+                "tagesschau 20:00 Uhr",
+                1696269600,
+                Some(15),
+            ),
+            item(
+                "ARD",
+                "tagesschau",
+                "tagesschau 12:00 Uhr",
+                1696240800,
+                Some(10),
+            ),
+            item("ZDF", "heute", "heute 19:00 Uhr", 1696266000, Some(20)),
+            item("ARD", "Sturm der Liebe", "Folge 4000", 1696150000, Some(50)),
+            item("ARD", "Livestream", "Das Erste Livestream", 0, None),
+            item("3Sat", "Wissenschaft", "Über Größe", 4102444800, Some(45)),
+        ])
+    }
+
+    fn titles(mediathek: &LocalMediathek, query: MediathekQuery) -> Vec<String> {
+        mediathek
+            .execute(&query)
+            .results
+            .into_iter()
+            .map(|item| item.title)
+            .collect()
+    }
+
+    #[test]
+    fn test_matching() {
+        let mediathek = mediathek();
+
+        assert_eq!(
+            titles(
+                &mediathek,
+                MediathekQuery::from_search_string("#TAGESSCH +20", false).unwrap()
+            ),
+            ["tagesschau 20:00 Uhr"]
+        );
+        assert_eq!(
+            titles(
+                &mediathek,
+                MediathekQuery::from_search_string("uber grosse", false).unwrap()
+            ),
+            ["Über Größe"]
+        );
+        assert_eq!(
+            titles(
+                &mediathek,
+                MediathekQuery {
+                    queries: vec![Query {
+                        fields: vec![QueryField::Channel, QueryField::Topic],
+                        query: "ard liebe".into(),
+                    }],
+                    ..Default::default()
+                }
+            ),
+            ["Folge 4000"]
+        );
+        assert!(titles(
+            &mediathek,
+            MediathekQuery::from_search_string("!zdf #tagesschau", false).unwrap()
+        )
+        .is_empty());
+        assert_eq!(
+            titles(
+                &mediathek,
+                MediathekQuery::from_search_string("!zdf | #tagesschau -12 uhr", false).unwrap()
+            ),
+            ["tagesschau 20:00 Uhr", "heute 19:00 Uhr"]
+        );
+        assert_eq!(
+            titles(
+                &mediathek,
+                MediathekQuery {
+                    channels: vec![Channel::Zdf, Channel::ThreeSat],
+                    ..Default::default()
+                }
+            ),
+            ["heute 19:00 Uhr", "Über Größe"]
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        let mediathek = mediathek();
+
+        assert_eq!(
+            titles(
+                &mediathek,
+                MediathekQuery::from_search_string("!ard >12 <50", false).unwrap()
+            ),
+            ["tagesschau 20:00 Uhr", "Folge 4000"]
+        );
+        assert_eq!(
+            titles(
+                &mediathek,
+                MediathekQuery {
+                    future: Some(false),
+                    ..MediathekQuery::from_search_string("!3sat", false).unwrap()
+                }
+            ),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            titles(
+                &mediathek,
+                MediathekQuery::from_search_string("!3sat", false).unwrap()
+            ),
+            ["Über Größe"]
+        );
+    }
+
+    #[test]
+    fn test_sorting_and_paging() {
+        let mediathek = mediathek();
+
+        let result = mediathek.execute(&MediathekQuery {
+            sort_by: Some(SortField::Timestamp),
+            size: Some(2),
+            offset: Some(1),
+            ..MediathekQuery::from_search_string("!ard", false).unwrap()
+        });
+        assert_eq!(result.query_info.total_results, 4);
+        assert_eq!(result.query_info.result_count, 2);
+        assert_eq!(result.query_info.filmliste_timestamp, 1698083280);
+        assert_eq!(
+            result
+                .results
+                .iter()
+                .map(|item| item.title.as_str())
+                .collect::<Vec<_>>(),
+            ["tagesschau 12:00 Uhr", "Folge 4000"]
+        );
+
+        assert_eq!(
+            titles(
+                &mediathek,
+                MediathekQuery {
+                    sort_by: Some(SortField::Duration),
+                    sort_order: Some(SortOrder::Ascending),
+                    ..Default::default()
+                }
+            ),
+            [
+                "Das Erste Livestream",
+                "tagesschau 12:00 Uhr",
+                "tagesschau 20:00 Uhr",
+                "heute 19:00 Uhr",
+                "Über Größe",
+                "Folge 4000"
+            ]
+        );
+    }
+}
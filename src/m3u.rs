@@ -0,0 +1,234 @@
+//! Export of items as extended M3U playlists for media players.
+//!
+//! Every item is written as an `#EXTINF` entry with its duration in seconds
+//! (`-1` for livestreams and other items without a duration) and a label of
+//! the form `channel – topic – title`, followed by the URL of its video in
+//! the selected [`Quality`]. Items without a video in an acceptable quality
+//! are skipped.
+//!
+//! # Example
+//! ```rust
+//! use mediathekviewweb::{
+//!     m3u::M3u,
+//!     models::{Fallback, Quality},
+//! };
+//!
+//! # let items: Vec<mediathekviewweb::models::Item> = Vec::new();
+//! let playlist = M3u::new()
+//!     .quality(Quality::Normal)
+//!     .fallback(Fallback::Any)
+//!     .render(&items);
+//! assert_eq!(playlist, "#EXTM3U\n");
+//! ```
+//!
+//! Streams returned by
+//! [`MediathekQueryBuilder::items`](crate::MediathekQueryBuilder::items) can be
+//! written using [`M3u::write_stream`].
+
+use std::{borrow::Borrow, io::Write};
+
+use futures_core::Stream;
+use futures_util::StreamExt;
+
+use crate::models::{Fallback, Item, Quality, QueryResult};
+
+/// The options of an exported playlist.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct M3u {
+    /// The preferred quality of the videos.
+    pub quality: Quality,
+    /// Which quality is chosen if a video isn't available in the preferred
+    /// quality.
+    pub fallback: Fallback,
+    /// Only include [livestreams](Item::is_livestream).
+    pub livestreams_only: bool,
+}
+impl Default for M3u {
+    fn default() -> Self {
+        Self {
+            quality: Quality::Hd,
+            fallback: Fallback::Lower,
+            livestreams_only: false,
+        }
+    }
+}
+impl M3u {
+    /// Create playlists with the highest available quality of every video.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the preferred quality of the videos.
+    pub fn quality(mut self, quality: Quality) -> Self {
+        self.quality = quality;
+        self
+    }
+    /// Set the quality that is chosen if a video isn't available in the
+    /// preferred quality.
+    pub fn fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+    /// Only include livestreams, e.g. to create a playlist of TV channels.
+    pub fn livestreams_only(mut self, livestreams_only: bool) -> Self {
+        self.livestreams_only = livestreams_only;
+        self
+    }
+
+    /// Return the `#EXTINF` line and the URL of an item, or `None` if the item
+    /// is skipped.
+    pub fn entry(&self, item: &Item) -> Option<String> {
+        if self.livestreams_only && !item.is_livestream() {
+            return None;
+        }
+        let (_, url) = item.select_url(self.quality, self.fallback)?;
+        let duration = item
+            .duration
+            .map_or(-1, |duration| duration.as_secs() as i64);
+        Some(format!(
+            "#EXTINF:{duration},{}\n{}\n",
+            single_line(&item.label()),
+            single_line(url)
+        ))
+    }
+
+    /// Write a playlist of items or references to items.
+    pub fn write<I>(&self, mut writer: impl Write, items: I) -> crate::Result<()>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Item>,
+    {
+        writer.write_all(HEADER.as_bytes())?;
+        for item in items {
+            if let Some(entry) = self.entry(item.borrow()) {
+                writer.write_all(entry.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a playlist of the items of a query result.
+    pub fn write_result(&self, writer: impl Write, result: &QueryResult) -> crate::Result<()> {
+        self.write(writer, &result.results)
+    }
+
+    /// Write a playlist of the items of a stream, e.g. one returned by
+    /// [`MediathekQueryBuilder::items`](crate::MediathekQueryBuilder::items).
+    ///
+    /// Entries are written as the items arrive. The first error of the stream
+    /// is returned, leaving an incomplete playlist.
+    pub async fn write_stream(
+        &self,
+        mut writer: impl Write,
+        items: impl Stream<Item = crate::Result<Item>>,
+    ) -> crate::Result<()> {
+        writer.write_all(HEADER.as_bytes())?;
+        let mut items = std::pin::pin!(items);
+        while let Some(item) = items.next().await {
+            if let Some(entry) = self.entry(&item?) {
+                writer.write_all(entry.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Return a playlist of items or references to items.
+    pub fn render<I>(&self, items: I) -> String
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Item>,
+    {
+        let mut playlist = String::from(HEADER);
+        playlist.extend(
+            items
+                .into_iter()
+                .filter_map(|item| self.entry(item.borrow())),
+        );
+        playlist
+    }
+}
+
+const HEADER: &str = "#EXTM3U\n";
+
+/// Replaces line breaks, which would end an entry early.
+fn single_line(s: &str) -> String {
+    s.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::M3u;
+    use crate::{
+        mock::fixtures,
+        models::{Fallback, Quality},
+    };
+
+    #[test]
+    fn test_render() {
+        let items = fixtures();
+        let playlist = M3u::new().render(&items[..4]);
+        assert_eq!(
+            playlist.lines().take(3).collect::<Vec<_>>(),
+            [
+                "#EXTM3U",
+                "#EXTINF:932,ARD – tagesschau – tagesschau 20:00 Uhr",
+                items[0].url_video_hd.as_deref().unwrap()
+            ]
+        );
+        assert_eq!(playlist.lines().nth(8), Some(items[3].url_video.as_str()));
+
+        // the 12:00 Uhr edition is only available in normal quality
+        let playlist = M3u::new()
+            .quality(Quality::Low)
+            .fallback(Fallback::None)
+            .render(&items[..4]);
+        assert_eq!(playlist.lines().count(), 1 + 3 * 2);
+
+        let playlist = M3u::new().livestreams_only(true).render(&items);
+        assert_eq!(
+            playlist,
+            format!(
+                "#EXTM3U\n\
+                 #EXTINF:-1,ARD – Livestream – Das Erste Livestream\n{}\n\
+                 #EXTINF:-1,ZDF – Livestream – ZDF Livestream\n{}\n",
+                items[5].url_video, items[6].url_video
+            )
+        );
+
+        let mut item = items[0].clone();
+        item.title = "tagesschau\n20:00 Uhr".into();
+        assert_eq!(
+            M3u::new().entry(&item).unwrap().lines().next(),
+            Some("#EXTINF:932,ARD – tagesschau – tagesschau 20:00 Uhr")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_stream() -> crate::Result<()> {
+        let items = fixtures();
+        let mut playlist = Vec::new();
+        M3u::new()
+            .write_stream(
+                &mut playlist,
+                futures_util::stream::iter(items.clone().into_iter().map(Ok)),
+            )
+            .await?;
+        assert_eq!(
+            String::from_utf8(playlist).unwrap(),
+            M3u::new().render(&items)
+        );
+
+        let mut playlist = Vec::new();
+        let result = M3u::new()
+            .write_stream(
+                &mut playlist,
+                futures_util::stream::iter([
+                    Ok(items[0].clone()),
+                    Err(crate::Error::EmptyResponse),
+                ]),
+            )
+            .await;
+        assert!(matches!(result, Err(crate::Error::EmptyResponse)));
+        assert_eq!(playlist.iter().filter(|&&b| b == b'\n').count(), 3);
+        Ok(())
+    }
+}
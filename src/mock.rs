@@ -0,0 +1,330 @@
+//! A local MediathekViewWeb server for offline tests.
+//!
+//! [`MockServer`] implements the `/api/query` endpoint on a random local port
+//! and answers queries from a fixed set of [`Item`]s using
+//! [`LocalMediathek`]. It can also [serve files](MockServer::serve_file), e.g.
+//! to test downloads. By default, it serves a set of [`fixtures`] that covers
+//! some edge cases of the official server's responses, e.g. livestreams that
+//! have an empty string as their duration and timestamps that are encoded as
+//! strings.
+//!
+//! # Example
+//! ```rust
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use mediathekviewweb::{mock::MockServer, models::QueryField, Mediathek};
+//!
+//! let server = MockServer::start()?;
+//! let mediathek = Mediathek::new_with_url(server.url(), "test".try_into()?)?;
+//!
+//! let results = mediathek.query([QueryField::Topic], "livestream").await?;
+//! assert_eq!(results.results.len(), 2);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{BufRead, BufReader, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Instant,
+};
+
+use serde_json::{json, Value};
+
+use crate::{local::LocalMediathek, models::Item};
+
+const FIXTURES: &str = include_str!("mock/fixtures.json");
+
+/// The items that are served by [`MockServer::start`].
+pub fn fixtures() -> Vec<Item> {
+    serde_json::from_str(FIXTURES).expect("fixtures are valid")
+}
+
+/// A local HTTP server that imitates a MediathekViewWeb server.
+///
+/// The server is shut down when this value is dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    failures: Arc<Mutex<VecDeque<Failure>>>,
+    request_count: Arc<AtomicUsize>,
+    files: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    thread: Option<JoinHandle<()>>,
+}
+impl MockServer {
+    /// Start a server that serves the default [`fixtures`].
+    pub fn start() -> std::io::Result<Self> {
+        let raw: Vec<Value> = serde_json::from_str(FIXTURES)?;
+        Self::start_with(fixtures().into_iter().zip(raw).collect())
+    }
+
+    /// Start a server that serves the specified items.
+    pub fn with_items(items: impl IntoIterator<Item = Item>) -> std::io::Result<Self> {
+        Self::start_with(
+            items
+                .into_iter()
+                .map(|item| {
+                    let raw = serde_json::to_value(&item)?;
+                    Ok((item, raw))
+                })
+                .collect::<std::io::Result<_>>()?,
+        )
+    }
+
+    fn start_with(items: Vec<(Item, Value)>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let addr = listener.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let failures = Arc::new(Mutex::new(VecDeque::new()));
+        let request_count = Arc::new(AtomicUsize::new(0));
+        let files = Arc::new(Mutex::new(HashMap::new()));
+
+        let raw = items
+            .iter()
+            .map(|(item, raw)| (item.id.clone(), raw.clone()))
+            .collect();
+        let state = State {
+            mediathek: LocalMediathek::new(
+                items.into_iter().map(|(item, _)| item).collect::<Vec<_>>(),
+            ),
+            raw,
+            failures: failures.clone(),
+            request_count: request_count.clone(),
+            files: files.clone(),
+        };
+
+        let thread = std::thread::spawn({
+            let shutdown = shutdown.clone();
+            move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::Acquire) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        // errors only affect a single connection
+                        let _ = state.handle(stream);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            shutdown,
+            failures,
+            request_count,
+            files,
+            thread: Some(thread),
+        })
+    }
+
+    /// The base URL of the server, e.g. `http://127.0.0.1:12345`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Answer the next request that has not been answered by a previous
+    /// failure with the specified status code and an optional `Retry-After`
+    /// header in seconds.
+    ///
+    /// Can be called multiple times to let several requests fail.
+    pub fn fail_next(&self, status: u16, retry_after: Option<u64>) {
+        self.failures
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push_back(Failure {
+                status,
+                retry_after,
+            });
+    }
+
+    /// Serve a file at `path`, e.g. `/video.mp4`, supporting range requests.
+    ///
+    /// The file is sent with an `ETag` that changes with its content, so
+    /// range requests with an outdated `If-Range` header return the entire
+    /// file.
+    pub fn serve_file(&self, path: impl Into<String>, content: impl Into<Vec<u8>>) {
+        self.files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(path.into(), content.into());
+    }
+
+    /// The count of requests that have been received, including failed ones.
+    pub fn request_count(&self) -> usize {
+        self.request_count.load(Ordering::Acquire)
+    }
+}
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Release);
+        // wake up the listener thread
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A failure that is returned instead of a regular response.
+#[derive(Debug)]
+struct Failure {
+    status: u16,
+    retry_after: Option<u64>,
+}
+
+struct State {
+    mediathek: LocalMediathek,
+    /// The serialized items as they are returned by the server.
+    raw: HashMap<String, Value>,
+    failures: Arc<Mutex<VecDeque<Failure>>>,
+    request_count: Arc<AtomicUsize>,
+    files: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+impl State {
+    fn handle(&self, stream: TcpStream) -> std::io::Result<()> {
+        let mut reader = BufReader::new(&stream);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+        let mut content_length = 0;
+        let mut range = None;
+        let mut if_range = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                } else if name.eq_ignore_ascii_case("range") {
+                    range = value
+                        .trim()
+                        .strip_prefix("bytes=")
+                        .and_then(|range| range.strip_suffix('-'))
+                        .and_then(|start| start.parse::<usize>().ok());
+                } else if name.eq_ignore_ascii_case("if-range") {
+                    if_range = Some(value.trim().to_owned());
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+
+        self.request_count.fetch_add(1, Ordering::AcqRel);
+        let failure = self
+            .failures
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front();
+
+        let mut headers = String::new();
+        let mut content_type = "application/json; charset=utf-8";
+        let (status, body) = if let Some(failure) = failure {
+            if let Some(retry_after) = failure.retry_after {
+                headers = format!("Retry-After: {retry_after}\r\n");
+            }
+            (
+                format!("{} Mock Failure", failure.status),
+                b"Mock Failure".to_vec(),
+            )
+        } else if let Some(file) = (method == "GET")
+            .then(|| {
+                self.files
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .get(path)
+                    .cloned()
+            })
+            .flatten()
+        {
+            let mut hasher = DefaultHasher::new();
+            file.hash(&mut hasher);
+            let etag = format!(r#""{:016x}""#, hasher.finish());
+            headers = format!("Accept-Ranges: bytes\r\nETag: {etag}\r\n");
+            content_type = "application/octet-stream";
+            if if_range.is_some_and(|if_range| if_range != etag) {
+                range = None;
+            }
+            match range {
+                Some(start) if start >= file.len() => {
+                    headers += &format!("Content-Range: bytes */{}\r\n", file.len());
+                    ("416 Range Not Satisfiable".to_owned(), Vec::new())
+                }
+                Some(start) => {
+                    headers += &format!(
+                        "Content-Range: bytes {start}-{}/{}\r\n",
+                        file.len() - 1,
+                        file.len()
+                    );
+                    ("206 Partial Content".to_owned(), file[start..].to_vec())
+                }
+                None => ("200 OK".to_owned(), file),
+            }
+        } else {
+            let (status, body) = match (method, path) {
+                ("POST", "/api/query") => self.query(&body),
+                _ => ("404 Not Found", "Not Found".to_owned()),
+            };
+            (status.to_owned(), body.into_bytes())
+        };
+
+        let mut stream = &stream;
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\n\
+             Content-Type: {content_type}\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\
+             {headers}\
+             \r\n",
+            body.len()
+        )?;
+        stream.write_all(&body)?;
+        stream.flush()
+    }
+
+    fn query(&self, body: &[u8]) -> (&'static str, String) {
+        let start = Instant::now();
+        let query = match serde_json::from_slice(body) {
+            Ok(query) => query,
+            Err(e) => {
+                return (
+                    "400 Bad Request",
+                    json!({ "err": [e.to_string()], "result": null }).to_string(),
+                )
+            }
+        };
+        let result = self.mediathek.execute(&query);
+
+        let body = json!({
+            "err": null,
+            "result": {
+                "queryInfo": {
+                    "filmlisteTimestamp": result.query_info.filmliste_timestamp.to_string(),
+                    "resultCount": result.query_info.result_count,
+                    "searchEngineTime": format!("{:.2}", start.elapsed().as_secs_f32()),
+                    "totalResults": result.query_info.total_results,
+                },
+                "results": result
+                    .results
+                    .iter()
+                    .map(|item| &self.raw[&item.id])
+                    .collect::<Vec<_>>(),
+            },
+        });
+        ("200 OK", body.to_string())
+    }
+}
@@ -7,11 +7,87 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
+mod channel;
+
+pub use channel::{Channel, Country};
+
+/// The request body of the `/api/query` endpoint.
+///
+/// Usually, this is created using the methods of
+/// [`MediathekQueryBuilder`](crate::MediathekQueryBuilder).
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MediathekQuery {
+    pub queries: Vec<Query>,
+    /// Queries that must not match.
+    ///
+    /// The server doesn't support negation, so these are applied by the
+    /// client after retrieving the results.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<Query>,
+    /// Groups of queries of which at least one query per group must match.
+    ///
+    /// The server doesn't support `OR`, so the client sends one request per
+    /// combination of alternatives and merges the results.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub any: Vec<Vec<Query>>,
+    /// Only include media of these channels.
+    ///
+    /// Unlike a [`Query`] for the channel field, which matches all channels
+    /// whose names contain the same words, this only matches the exact
+    /// channels. The client queries each channel separately and filters the
+    /// results.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<Channel>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "optional_duration_secs"
+    )]
+    pub duration_min: Option<Duration>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "optional_duration_secs"
+    )]
+    pub duration_max: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub future: Option<bool>,
+    /// Only include media that are broadcast at or after this unix
+    /// timestamp.
+    ///
+    /// The server doesn't support date filters, so this is applied by the
+    /// client after retrieving the results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broadcast_after: Option<i64>,
+    /// Only include media that are broadcast before this unix timestamp.
+    ///
+    /// The server doesn't support date filters, so this is applied by the
+    /// client after retrieving the results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broadcast_before: Option<i64>,
+    #[serde(rename = "sortBy", skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<SortField>,
+    #[serde(rename = "sortOrder", skip_serializing_if = "Option::is_none")]
+    pub sort_order: Option<SortOrder>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Query {
     pub fields: Vec<QueryField>,
     pub query: String,
 }
+impl Query {
+    /// Create a query that searches for `query` in `fields`.
+    pub fn new(fields: impl Into<Vec<QueryField>>, query: impl Into<String>) -> Self {
+        Self {
+            fields: fields.into(),
+            query: query.into(),
+        }
+    }
+}
 
 #[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[non_exhaustive]
@@ -50,7 +126,7 @@ pub enum SortOrder {
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Item {
-    pub channel: String,
+    pub channel: Channel,
     pub topic: String,
     pub title: String,
     #[serde(deserialize_with = "empty_string_as_none")]
@@ -71,6 +147,100 @@ pub struct Item {
     pub filmliste_timestamp: i64,
     pub id: String,
 }
+impl Item {
+    /// Check whether this item is a livestream of a channel.
+    ///
+    /// MediathekView lists livestreams under the topic `Livestream`. They have
+    /// no duration and their videos are usually HLS playlists.
+    pub fn is_livestream(&self) -> bool {
+        self.topic.eq_ignore_ascii_case("livestream")
+    }
+
+    /// Return a label of the form `channel – topic – title`, e.g. for the
+    /// entries of playlists and feeds.
+    pub fn label(&self) -> String {
+        format!("{} – {} – {}", self.channel, self.topic, self.title)
+    }
+
+    /// Return the URL of the video in a specific quality, if available.
+    pub fn url_for(&self, quality: Quality) -> Option<&str> {
+        match quality {
+            Quality::Low => self.url_video_low.as_deref(),
+            Quality::Normal => Some(self.url_video.as_str()),
+            Quality::Hd => self.url_video_hd.as_deref(),
+        }
+        .filter(|url| !url.is_empty())
+    }
+
+    /// Return all qualities in which the video is available, from lowest to
+    /// highest.
+    pub fn available_qualities(&self) -> Vec<Quality> {
+        Quality::ALL
+            .iter()
+            .copied()
+            .filter(|&quality| self.url_for(quality).is_some())
+            .collect()
+    }
+
+    /// Return the highest available quality and its URL.
+    pub fn best_url(&self) -> Option<(Quality, &str)> {
+        self.select_url(Quality::Hd, Fallback::Lower)
+    }
+
+    /// Return the URL of the video in the preferred quality or, if it is not
+    /// available, in another quality chosen by `fallback`.
+    ///
+    /// For example, `item.select_url(Quality::Hd, Fallback::Lower)` returns the
+    /// HD video, else the normal video, else the low quality video.
+    pub fn select_url(&self, preferred: Quality, fallback: Fallback) -> Option<(Quality, &str)> {
+        let lower = Quality::ALL.iter().rev().filter(|&&q| q < preferred);
+        let higher = Quality::ALL.iter().filter(|&&q| q > preferred);
+        let candidates: Vec<Quality> = match fallback {
+            Fallback::None => vec![preferred],
+            Fallback::Lower => [preferred].into_iter().chain(lower.copied()).collect(),
+            Fallback::Higher => [preferred].into_iter().chain(higher.copied()).collect(),
+            Fallback::Any => [preferred]
+                .into_iter()
+                .chain(lower.copied())
+                .chain(higher.copied())
+                .collect(),
+        };
+        candidates
+            .into_iter()
+            .find_map(|quality| Some((quality, self.url_for(quality)?)))
+    }
+}
+
+/// The quality of an [`Item`]'s video, ordered from lowest to highest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    /// [`Item::url_video_low`]
+    Low,
+    /// [`Item::url_video`]
+    Normal,
+    /// [`Item::url_video_hd`]
+    Hd,
+}
+impl Quality {
+    pub const ALL: &'static [Quality] = &[Quality::Low, Quality::Normal, Quality::Hd];
+}
+
+/// Describes which quality is chosen by [`Item::select_url`] if the preferred
+/// quality is not available.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fallback {
+    /// Don't fall back to another quality.
+    None,
+    /// Fall back to the next lower quality.
+    #[default]
+    Lower,
+    /// Fall back to the next higher quality.
+    Higher,
+    /// Fall back to lower qualities first, then to higher qualities.
+    Any,
+}
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -93,8 +263,8 @@ pub struct QueryInfo {
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ApiResult<T> {
-    err: Option<ApiError>,
-    result: Option<T>,
+    pub(crate) err: Option<ApiError>,
+    pub(crate) result: Option<T>,
 }
 impl<T> From<ApiResult<T>> for crate::Result<T> {
     fn from(result: ApiResult<T>) -> crate::Result<T> {
@@ -145,7 +315,7 @@ mod duration_millisecs {
     }
 }
 
-mod optional_duration_secs {
+pub(crate) mod optional_duration_secs {
     use std::time::Duration;
 
     use serde::{Deserializer, Serialize, Serializer};
@@ -184,6 +354,13 @@ mod optional_duration_secs {
                 Ok(None)
             }
 
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(None)
+            }
+
             fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Self::Value, E> {
                 if s.is_empty() {
                     Ok(None)
@@ -244,10 +421,44 @@ pub fn empty_string_as_none<'de, D>(deserializer: D) -> Result<Option<String>, D
 where
     D: serde::de::Deserializer<'de>,
 {
-    let s = String::deserialize(deserializer)?;
-    if s.is_empty() {
-        Ok(None)
-    } else {
-        Ok(Some(s))
+    let s = Option::<String>::deserialize(deserializer)?;
+    Ok(s.filter(|s| !s.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fallback, Quality};
+    use crate::mock::fixtures;
+
+    #[test]
+    fn test_quality() {
+        let mut item = fixtures().remove(0);
+        assert_eq!(
+            item.available_qualities(),
+            [Quality::Low, Quality::Normal, Quality::Hd]
+        );
+        assert_eq!(
+            item.best_url(),
+            Some((Quality::Hd, item.url_video_hd.as_deref().unwrap()))
+        );
+
+        item.url_video_hd = None;
+        item.url_video_low = Some(String::new());
+        assert_eq!(item.available_qualities(), [Quality::Normal]);
+        assert_eq!(item.url_for(Quality::Low), None);
+        assert_eq!(
+            item.best_url(),
+            Some((Quality::Normal, item.url_video.as_str()))
+        );
+        assert_eq!(item.select_url(Quality::Hd, Fallback::None), None);
+        assert_eq!(item.select_url(Quality::Low, Fallback::Lower), None);
+        assert_eq!(
+            item.select_url(Quality::Low, Fallback::Higher),
+            Some((Quality::Normal, item.url_video.as_str()))
+        );
+        assert_eq!(
+            item.select_url(Quality::Hd, Fallback::Any),
+            Some((Quality::Normal, item.url_video.as_str()))
+        );
     }
 }
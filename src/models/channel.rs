@@ -0,0 +1,451 @@
+use std::{
+    convert::Infallible,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{Query, QueryField};
+
+/// The channel of an [`Item`](super::Item).
+///
+/// Channels are identified by the names used in MediathekView's Filmliste,
+/// e.g. `ARD` or `3Sat`, which are parsed case-insensitively. The names of
+/// known channels are normalized to the spelling of the Filmliste, so `ard`
+/// is parsed as [`Ard`](Channel::Ard) and serialized as `ARD`. Unknown
+/// channels are kept as [`Other`](Channel::Other) with their original
+/// spelling.
+///
+/// Channels are compared by their names, ignoring ASCII case, so an
+/// [`Other`](Channel::Other) channel with the name of a known channel is equal
+/// to the known channel:
+/// ```rust
+/// use mediathekviewweb::models::Channel;
+///
+/// assert_eq!(Channel::Other("ard".into()), Channel::Ard);
+/// assert_eq!(Channel::Ard, "ard");
+/// ```
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum Channel {
+    ThreeSat,
+    Ard,
+    ArteDe,
+    ArteEn,
+    ArteEs,
+    ArteFr,
+    ArteIt,
+    ArtePl,
+    Br,
+    Dw,
+    Funk,
+    Hr,
+    Kika,
+    Mdr,
+    Ndr,
+    Orf,
+    Phoenix,
+    RadioBremen,
+    Rbb,
+    Sr,
+    Srf,
+    SrfPodcast,
+    Swr,
+    Wdr,
+    Zdf,
+    ZdfTivi,
+    /// A channel that is not known to this library.
+    Other(String),
+}
+
+/// The country in which a broadcaster is based.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Country {
+    Germany,
+    Austria,
+    Switzerland,
+    France,
+}
+impl Country {
+    /// Return the ISO 3166-1 alpha-2 code of this country.
+    pub fn code(self) -> &'static str {
+        match self {
+            Country::Germany => "DE",
+            Country::Austria => "AT",
+            Country::Switzerland => "CH",
+            Country::France => "FR",
+        }
+    }
+}
+
+/// The metadata of a known channel.
+struct Info {
+    name: &'static str,
+    display_name: &'static str,
+    country: Country,
+    parent: Option<Channel>,
+    website: &'static str,
+}
+
+impl Channel {
+    /// All known channels.
+    pub const KNOWN: &'static [Channel] = &[
+        Channel::ThreeSat,
+        Channel::Ard,
+        Channel::ArteDe,
+        Channel::ArteEn,
+        Channel::ArteEs,
+        Channel::ArteFr,
+        Channel::ArteIt,
+        Channel::ArtePl,
+        Channel::Br,
+        Channel::Dw,
+        Channel::Funk,
+        Channel::Hr,
+        Channel::Kika,
+        Channel::Mdr,
+        Channel::Ndr,
+        Channel::Orf,
+        Channel::Phoenix,
+        Channel::RadioBremen,
+        Channel::Rbb,
+        Channel::Sr,
+        Channel::Srf,
+        Channel::SrfPodcast,
+        Channel::Swr,
+        Channel::Wdr,
+        Channel::Zdf,
+        Channel::ZdfTivi,
+    ];
+
+    /// Return the name of this channel as used in the Filmliste and by the
+    /// API, e.g. `ARTE.DE`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Channel::Other(name) => name,
+            channel => channel.info().map_or("", |info| info.name),
+        }
+    }
+
+    /// Return a human-readable name of this channel, e.g. `ARTE (Deutsch)`.
+    pub fn display_name(&self) -> &str {
+        match self {
+            Channel::Other(name) => name,
+            channel => channel.info().map_or("", |info| info.display_name),
+        }
+    }
+
+    /// Return the country in which the broadcaster is based, if the channel is
+    /// known.
+    pub fn country(&self) -> Option<Country> {
+        self.info().map(|info| info.country)
+    }
+
+    /// Return the network this channel belongs to, e.g. [`Ard`](Channel::Ard)
+    /// for the regional broadcasters of the ARD.
+    ///
+    /// Channels that are run jointly by several networks, like
+    /// [`ThreeSat`](Channel::ThreeSat), have no parent.
+    pub fn parent(&self) -> Option<Channel> {
+        self.info().and_then(|info| info.parent)
+    }
+
+    /// Return the URL of the channel's website, if the channel is known.
+    pub fn website(&self) -> Option<&'static str> {
+        self.info().map(|info| info.website)
+    }
+
+    /// Return a query that searches for this channel.
+    ///
+    /// The server also returns channels whose names contain the same words,
+    /// e.g. `ZDF-tivi` for `ZDF`. Use
+    /// [`MediathekQueryBuilder::channel`](crate::MediathekQueryBuilder::channel)
+    /// to only include results of this channel.
+    pub fn query(&self) -> Query {
+        Query::new([QueryField::Channel], self.as_str())
+    }
+
+    fn info(&self) -> Option<Info> {
+        use Country::*;
+
+        let (name, display_name, country, parent, website) = match self {
+            Channel::ThreeSat => ("3Sat", "3sat", Germany, None, "https://www.3sat.de"),
+            Channel::Ard => ("ARD", "ARD", Germany, None, "https://www.ardmediathek.de"),
+            Channel::ArteDe => (
+                "ARTE.DE",
+                "ARTE (Deutsch)",
+                France,
+                None,
+                "https://www.arte.tv/de/",
+            ),
+            Channel::ArteEn => (
+                "ARTE.EN",
+                "ARTE (English)",
+                France,
+                None,
+                "https://www.arte.tv/en/",
+            ),
+            Channel::ArteEs => (
+                "ARTE.ES",
+                "ARTE (Español)",
+                France,
+                None,
+                "https://www.arte.tv/es/",
+            ),
+            Channel::ArteFr => (
+                "ARTE.FR",
+                "ARTE (Français)",
+                France,
+                None,
+                "https://www.arte.tv/fr/",
+            ),
+            Channel::ArteIt => (
+                "ARTE.IT",
+                "ARTE (Italiano)",
+                France,
+                None,
+                "https://www.arte.tv/it/",
+            ),
+            Channel::ArtePl => (
+                "ARTE.PL",
+                "ARTE (Polski)",
+                France,
+                None,
+                "https://www.arte.tv/pl/",
+            ),
+            Channel::Br => (
+                "BR",
+                "Bayerischer Rundfunk",
+                Germany,
+                Some(Channel::Ard),
+                "https://www.br.de",
+            ),
+            Channel::Dw => (
+                "DW",
+                "Deutsche Welle",
+                Germany,
+                Some(Channel::Ard),
+                "https://www.dw.com",
+            ),
+            Channel::Funk => ("Funk.net", "funk", Germany, None, "https://www.funk.net"),
+            Channel::Hr => (
+                "HR",
+                "Hessischer Rundfunk",
+                Germany,
+                Some(Channel::Ard),
+                "https://www.hr.de",
+            ),
+            Channel::Kika => ("KiKA", "KiKA", Germany, None, "https://www.kika.de"),
+            Channel::Mdr => (
+                "MDR",
+                "Mitteldeutscher Rundfunk",
+                Germany,
+                Some(Channel::Ard),
+                "https://www.mdr.de",
+            ),
+            Channel::Ndr => (
+                "NDR",
+                "Norddeutscher Rundfunk",
+                Germany,
+                Some(Channel::Ard),
+                "https://www.ndr.de",
+            ),
+            Channel::Orf => ("ORF", "ORF", Austria, None, "https://on.orf.at"),
+            Channel::Phoenix => (
+                "PHOENIX",
+                "phoenix",
+                Germany,
+                None,
+                "https://www.phoenix.de",
+            ),
+            Channel::RadioBremen => (
+                "Radio Bremen TV",
+                "Radio Bremen",
+                Germany,
+                Some(Channel::Ard),
+                "https://www.radiobremen.de",
+            ),
+            Channel::Rbb => (
+                "RBB",
+                "Rundfunk Berlin-Brandenburg",
+                Germany,
+                Some(Channel::Ard),
+                "https://www.rbb-online.de",
+            ),
+            Channel::Sr => (
+                "SR",
+                "Saarländischer Rundfunk",
+                Germany,
+                Some(Channel::Ard),
+                "https://www.sr.de",
+            ),
+            Channel::Srf => (
+                "SRF",
+                "Schweizer Radio und Fernsehen",
+                Switzerland,
+                None,
+                "https://www.srf.ch",
+            ),
+            Channel::SrfPodcast => (
+                "SRF.Podcast",
+                "SRF Podcast",
+                Switzerland,
+                Some(Channel::Srf),
+                "https://www.srf.ch/audio",
+            ),
+            Channel::Swr => (
+                "SWR",
+                "Südwestrundfunk",
+                Germany,
+                Some(Channel::Ard),
+                "https://www.swr.de",
+            ),
+            Channel::Wdr => (
+                "WDR",
+                "Westdeutscher Rundfunk",
+                Germany,
+                Some(Channel::Ard),
+                "https://www1.wdr.de",
+            ),
+            Channel::Zdf => ("ZDF", "ZDF", Germany, None, "https://www.zdf.de"),
+            Channel::ZdfTivi => (
+                "ZDF-tivi",
+                "ZDFtivi",
+                Germany,
+                Some(Channel::Zdf),
+                "https://www.zdf.de/kinder",
+            ),
+            Channel::Other(_) => return None,
+        };
+        Some(Info {
+            name,
+            display_name,
+            country,
+            parent,
+            website,
+        })
+    }
+}
+
+impl FromStr for Channel {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Channel::KNOWN
+            .iter()
+            .find(|channel| channel.as_str().eq_ignore_ascii_case(s))
+            .cloned()
+            .unwrap_or_else(|| Channel::Other(s.to_owned())))
+    }
+}
+impl From<&str> for Channel {
+    fn from(s: &str) -> Self {
+        let Ok(channel) = s.parse();
+        channel
+    }
+}
+impl From<String> for Channel {
+    fn from(s: String) -> Self {
+        match Channel::from(s.as_str()) {
+            Channel::Other(_) => Channel::Other(s),
+            channel => channel,
+        }
+    }
+}
+impl Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        self == other.as_str()
+    }
+}
+impl Eq for Channel {}
+impl PartialEq<str> for Channel {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str().eq_ignore_ascii_case(other)
+    }
+}
+impl PartialEq<&str> for Channel {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+impl Hash for Channel {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // consistent with the case-insensitive comparison
+        for byte in self.as_str().bytes() {
+            state.write_u8(byte.to_ascii_lowercase());
+        }
+        state.write_u8(0xff);
+    }
+}
+impl Serialize for Channel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_str().serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for Channel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Channel::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{Channel, Country};
+    use crate::mock::fixtures;
+
+    #[test]
+    fn test_channel() {
+        assert_eq!("3sat".parse(), Ok(Channel::ThreeSat));
+        assert_eq!(Channel::from("ARTE.DE"), Channel::ArteDe);
+        assert_eq!(
+            Channel::from("Example TV"),
+            Channel::Other("Example TV".into())
+        );
+        for channel in Channel::KNOWN {
+            assert_eq!(&Channel::from(channel.as_str()), channel);
+            assert!(channel.country().is_some());
+            assert!(channel
+                .website()
+                .is_some_and(|url| url.starts_with("https://")));
+        }
+
+        assert_eq!(Channel::Wdr.display_name(), "Westdeutscher Rundfunk");
+        assert_eq!(Channel::Wdr.parent(), Some(Channel::Ard));
+        assert_eq!(Channel::ThreeSat.parent(), None);
+        assert_eq!(Channel::Orf.country().map(Country::code), Some("AT"));
+        let other = Channel::Other("Example TV".into());
+        assert_eq!(other.display_name(), "Example TV");
+        assert_eq!(other.country(), None);
+        assert_eq!(other, Channel::from("example tv"));
+        assert_eq!(Channel::from("example tv").as_str(), "example tv");
+
+        // channels are compared by their names
+        assert_eq!(Channel::Other("ARD".into()), Channel::Ard);
+        assert_eq!(Channel::Ard, Channel::Other("ard".into()));
+        assert_ne!(Channel::Ard, Channel::ArteDe);
+        assert_eq!(Channel::Ard, "ard");
+        let channels = HashSet::from([Channel::Ard, Channel::Other("Example TV".into())]);
+        assert!(channels.contains(&Channel::Other("ARD".into())));
+        assert!(channels.contains(&Channel::Other("example TV".into())));
+        assert!(!channels.contains(&Channel::Zdf));
+
+        let channels = fixtures()
+            .into_iter()
+            .map(|item| item.channel)
+            .collect::<Vec<_>>();
+        assert_eq!(channels[0], Channel::Ard);
+        assert_eq!(channels[0], "ARD");
+        assert!(channels.contains(&Channel::ArteDe));
+        assert_eq!(serde_json::to_string(&channels[0]).unwrap(), r#""ARD""#);
+    }
+}
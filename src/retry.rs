@@ -0,0 +1,315 @@
+//! Retrying failed requests and limiting the request rate.
+//!
+//! # Example
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use mediathekviewweb::{
+//!     retry::{RateLimiter, RetryPolicy},
+//!     Mediathek,
+//! };
+//!
+//! fn polite(mediathek: Mediathek) -> Mediathek {
+//!     mediathek
+//!         .with_retry_policy(
+//!             RetryPolicy::new()
+//!                 .max_attempts(5)
+//!                 .initial_backoff(Duration::from_secs(2)),
+//!         )
+//!         .with_rate_limit(RateLimiter::new(Duration::from_secs(1)))
+//! }
+//! ```
+
+use std::{
+    hash::BuildHasher,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::Error;
+
+/// Describes if and when failed requests are retried.
+///
+/// The delay before the `n`-th retry is
+/// `initial_backoff * multiplier^(n - 1)`, limited to `max_backoff`. If jitter
+/// is enabled, the delay is randomly reduced by up to 50% to avoid many
+/// clients retrying at the same time. If the server sends a `Retry-After`
+/// header, its value is used instead, limited to `max_retry_after`.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_retry_after: Duration,
+    multiplier: f64,
+    jitter: bool,
+    retryable: fn(&Error) -> bool,
+}
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl RetryPolicy {
+    /// Create a policy that retries [retryable](is_retryable) errors up to
+    /// two times, waiting one second before the first retry and doubling the
+    /// delay after each attempt.
+    pub fn new() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            max_retry_after: Duration::from_secs(5 * 60),
+            multiplier: 2.0,
+            jitter: true,
+            retryable: is_retryable,
+        }
+    }
+    /// Create a policy that never retries failed requests.
+    pub fn never() -> Self {
+        Self::new().max_attempts(1)
+    }
+    /// Set the maximum count of attempts, including the first request.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+    /// Set the delay before the first retry.
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+    /// Set the maximum delay between two attempts.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+    /// Set the maximum delay requested by a `Retry-After` header.
+    ///
+    /// Longer delays are shortened to this value. Defaults to five minutes.
+    pub fn max_retry_after(mut self, max_retry_after: Duration) -> Self {
+        self.max_retry_after = max_retry_after;
+        self
+    }
+    /// Set the factor by which the delay increases after each attempt.
+    ///
+    /// Factors below `1.0` are treated as `1.0`.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+    /// Randomly reduce the delays by up to 50%.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+    /// Set the function that decides whether an error is retryable.
+    ///
+    /// Defaults to [`is_retryable`].
+    pub fn retryable(mut self, retryable: fn(&Error) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// Return the delay before the next attempt or `None` if the request
+    /// should not be retried.
+    ///
+    /// `attempt` is the count of attempts that have been made so far.
+    pub(crate) fn delay(
+        &self,
+        attempt: u32,
+        error: &Error,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts || !(self.retryable)(error) {
+            return None;
+        }
+        if let Some(retry_after) = retry_after {
+            return Some(retry_after.min(self.max_retry_after));
+        }
+
+        let exponent = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        // `Duration::mul_f64` panics on overflow, so the limit is applied first
+        let backoff = if backoff < self.max_backoff.as_secs_f64() {
+            Duration::try_from_secs_f64(backoff).unwrap_or(self.max_backoff)
+        } else {
+            self.max_backoff
+        };
+        if self.jitter {
+            let random = std::collections::hash_map::RandomState::new().hash_one(attempt);
+            let factor = 0.5 + (random as f64 / u64::MAX as f64) / 2.0;
+            Some(Duration::try_from_secs_f64(backoff.as_secs_f64() * factor).unwrap_or(backoff))
+        } else {
+            Some(backoff)
+        }
+    }
+}
+
+/// Returns `true` for errors that are likely temporary: failed connections,
+/// timeouts (including `408`), rate limiting (`429`) and server errors (`500`,
+/// `502`, `503`, `504`).
+///
+/// Errors of custom [`Transport`](crate::transport::Transport)s are always
+/// considered retryable.
+pub fn is_retryable(error: &Error) -> bool {
+    match error {
+        #[cfg(feature = "reqwest")]
+        Error::Reqwest(e) => e.is_connect() || e.is_timeout(),
+        #[cfg(feature = "reqwest")]
+        Error::Transport(e) if e.is::<reqwest::Error>() => e
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout()),
+        Error::Transport(_) => true,
+        Error::Status(status) => matches!(status, 408 | 429 | 500 | 502 | 503 | 504),
+        _ => false,
+    }
+}
+
+/// Parses the value of a `Retry-After` header, which is either a count of
+/// seconds or an HTTP date like `Wed, 21 Oct 2015 07:28:00 GMT`.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let mut parts = value.split_whitespace().skip(1);
+    let day = parts.next()?.parse().ok()?;
+    let month = match parts.next()? {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.splitn(3, ':').map(str::parse::<u32>);
+    let (hours, minutes, seconds) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+
+    let timestamp = crate::date::timestamp_from_civil(year, month, day, hours, minutes, seconds)?;
+    let date = UNIX_EPOCH + Duration::from_secs(u64::try_from(timestamp).ok()?);
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Limits the rate of requests sent by a client by enforcing a minimum
+/// interval between two requests.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+impl RateLimiter {
+    /// Create a rate limiter that waits at least `interval` between two
+    /// requests.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Mutex::new(None),
+        }
+    }
+    /// Create a rate limiter that allows at most `requests` per second.
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(Duration::from_secs(1) / requests.max(1))
+    }
+
+    /// Reserve a slot for a request and return how long to wait before
+    /// sending it.
+    pub(crate) fn reserve(&self) -> Duration {
+        let now = Instant::now();
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+        let slot = next.map_or(now, |next| next.max(now));
+        *next = Some(slot + self.interval);
+        slot - now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_retry_after, RateLimiter, RetryPolicy};
+    use crate::Error;
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::new()
+            .max_attempts(4)
+            .initial_backoff(Duration::from_secs(1))
+            .max_backoff(Duration::from_secs(3))
+            .jitter(false);
+        let error = Error::Status(503);
+
+        assert_eq!(policy.delay(1, &error, None), Some(Duration::from_secs(1)));
+        assert_eq!(policy.delay(2, &error, None), Some(Duration::from_secs(2)));
+        assert_eq!(policy.delay(3, &error, None), Some(Duration::from_secs(3)));
+        assert_eq!(policy.delay(4, &error, None), None);
+        assert_eq!(
+            policy.delay(1, &error, Some(Duration::from_secs(10))),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            policy.delay(1, &error, Some(Duration::from_secs(u64::MAX))),
+            Some(Duration::from_secs(5 * 60))
+        );
+        assert_eq!(policy.delay(1, &Error::Status(404), None), None);
+        assert_eq!(RetryPolicy::never().delay(1, &error, None), None);
+
+        let delay = RetryPolicy::new()
+            .jitter(true)
+            .delay(2, &error, None)
+            .unwrap();
+        assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_secs(2));
+
+        // large exponents and multipliers are limited to the maximum backoff
+        let policy = RetryPolicy::new()
+            .max_attempts(u32::MAX)
+            .multiplier(f64::MAX)
+            .max_backoff(Duration::MAX)
+            .jitter(false);
+        assert_eq!(policy.delay(1000, &error, None), Some(Duration::MAX));
+        let policy = RetryPolicy::new()
+            .max_attempts(u32::MAX)
+            .multiplier(0.5)
+            .jitter(false);
+        assert_eq!(
+            policy.delay(u32::MAX - 1, &error, None),
+            Some(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert!(parse_retry_after("Fri, 01 Jan 2100 00:00:00 GMT").unwrap() > Duration::ZERO);
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(
+            parse_retry_after("Fri, 01 Jan 99999999999999999 00:00:00 GMT"),
+            None
+        );
+        assert_eq!(parse_retry_after("Fri, 31 Feb 2100 00:00:00 GMT"), None);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let rate_limiter = RateLimiter::per_second(10);
+        assert_eq!(rate_limiter.reserve(), Duration::ZERO);
+        assert!(rate_limiter.reserve() > Duration::from_millis(90));
+        assert!(rate_limiter.reserve() > Duration::from_millis(190));
+    }
+}
@@ -0,0 +1,554 @@
+//! Parser for [MediathekViewWeb's advanced search syntax](https://github.com/mediathekview/mediathekviewweb/blob/master/README.md#erweiterte-suche).
+//!
+//! A search string consists of whitespace-separated terms. Terms starting
+//! with one of the following prefixes only search in a specific field:
+//!
+//! | Prefix | Meaning          | Example            |
+//! |--------|------------------|--------------------|
+//! | `!`    | Channel          | `!ard`             |
+//! | `#`    | Topic            | `#sturm,der,liebe` |
+//! | `+`    | Title            | `+gebärdensprache` |
+//! | `*`    | Description      | `*norwegen`        |
+//! | `>`    | Minimum duration | `>60`              |
+//! | `<`    | Maximum duration | `<90`              |
+//!
+//! Durations are given in minutes. In field values, commas are interpreted as
+//! spaces. Additionally, this parser supports quoted phrases (e.g.
+//! `#"sturm der liebe"`) and escaping of single characters using a backslash
+//! (e.g. `\!important` searches for `!important` as free text).
+//!
+//! The server only supports terms that must all match. This parser
+//! additionally supports the following operators, which are emulated by the
+//! client (see [`exclude`](crate::models::MediathekQuery::exclude) and
+//! [`any`](crate::models::MediathekQuery::any)):
+//!
+//! | Operator | Meaning                                  | Example        |
+//! |----------|------------------------------------------|----------------|
+//! | `-`      | Exclude results that match the term      | `-#wetter`     |
+//! | `\|`     | Match at least one of the adjacent terms | `!ard \| !zdf` |
+//!
+//! Only search terms can be negated or combined, not durations. `-` must be
+//! placed before a prefix. Use `\-` and `\|` to search for these characters.
+//!
+//! # Example
+//! ```rust
+//! use mediathekviewweb::search_string::{SearchString, Term};
+//!
+//! let search: SearchString = r#"!ard #"sturm der liebe" >30 folge"#.parse().unwrap();
+//! assert_eq!(
+//!     search.terms,
+//!     [
+//!         Term::Channel("ard".into()),
+//!         Term::Topic("sturm der liebe".into()),
+//!         Term::DurationMin(std::time::Duration::from_secs(30 * 60)),
+//!         Term::Text("folge".into()),
+//!     ]
+//! );
+//! assert_eq!(search.to_string(), "!ard #sturm,der,liebe >30 folge");
+//! ```
+
+use std::{
+    fmt::{Display, Write},
+    iter::Peekable,
+    ops::Range,
+    str::{CharIndices, FromStr},
+    time::Duration,
+};
+
+/// A parsed search string.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchString {
+    pub terms: Vec<Term>,
+}
+
+/// A single term of a [`SearchString`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Term {
+    /// Search in the channel (`!`).
+    Channel(String),
+    /// Search in the topic (`#`).
+    Topic(String),
+    /// Search in the title (`+`).
+    Title(String),
+    /// Search in the description (`*`).
+    Description(String),
+    /// Filter for a minimum duration (`>`).
+    DurationMin(Duration),
+    /// Filter for a maximum duration (`<`).
+    DurationMax(Duration),
+    /// Free text without a prefix.
+    Text(String),
+    /// Exclude results that match a channel, topic, title, description or
+    /// text term (`-`).
+    Not(Box<Term>),
+    /// Match results that match at least one of several channel, topic,
+    /// title, description or text terms (`|`).
+    Or(Vec<Term>),
+}
+impl Term {
+    /// Check whether this term searches for a value, i.e. it is not a
+    /// duration, negation or `|` group.
+    fn is_field_or_text(&self) -> bool {
+        matches!(
+            self,
+            Term::Channel(_)
+                | Term::Topic(_)
+                | Term::Title(_)
+                | Term::Description(_)
+                | Term::Text(_)
+        )
+    }
+}
+
+/// An error that occured while parsing a [`SearchString`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The kind of error.
+    pub kind: ParseErrorKind,
+    /// The byte range of the input that caused the error.
+    pub span: Range<usize>,
+}
+
+/// The kind of a [`ParseError`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// A prefix is not followed by a value.
+    EmptyValue,
+    /// A duration is not a whole number of minutes.
+    InvalidDuration,
+    /// A quoted phrase is missing its closing quote.
+    UnterminatedQuote,
+    /// The input ends with a backslash that doesn't escape anything.
+    TrailingEscape,
+    /// A `|` is not preceded or followed by a term.
+    MissingOperand,
+    /// A duration is negated or a duration or negation is combined using `|`.
+    InvalidOperand,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self.kind {
+            ParseErrorKind::EmptyValue => "missing value after prefix",
+            ParseErrorKind::InvalidDuration => "duration must be a whole number of minutes",
+            ParseErrorKind::UnterminatedQuote => "unterminated quote",
+            ParseErrorKind::TrailingEscape => "trailing escape character",
+            ParseErrorKind::MissingOperand => "missing term next to `|`",
+            ParseErrorKind::InvalidOperand => {
+                "only search terms can be negated or combined using `|`"
+            }
+        };
+        write!(f, "{message} at {}..{}", self.span.start, self.span.end)
+    }
+}
+impl std::error::Error for ParseError {}
+
+impl FromStr for SearchString {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut terms = Vec::new();
+        let mut chars = s.char_indices().peekable();
+
+        loop {
+            while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+            let Some(&(start, first)) = chars.peek() else {
+                break;
+            };
+            if first == '|' {
+                return Err(ParseError {
+                    kind: ParseErrorKind::MissingOperand,
+                    span: start..start + 1,
+                });
+            }
+
+            let (term, span) = parse_term(s, &mut chars)?;
+            let mut alternatives = vec![(term, span)];
+            loop {
+                let mut lookahead = chars.clone();
+                while lookahead.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+                let Some((bar, '|')) = lookahead.next() else {
+                    break;
+                };
+                while lookahead.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+                chars = lookahead;
+                if matches!(chars.peek(), None | Some((_, '|'))) {
+                    return Err(ParseError {
+                        kind: ParseErrorKind::MissingOperand,
+                        span: bar..bar + 1,
+                    });
+                }
+                alternatives.push(parse_term(s, &mut chars)?);
+            }
+
+            if alternatives.len() == 1 {
+                terms.extend(alternatives.into_iter().map(|(term, _)| term));
+                continue;
+            }
+            if let Some((_, span)) = alternatives
+                .iter()
+                .find(|(term, _)| !term.is_field_or_text())
+            {
+                return Err(ParseError {
+                    kind: ParseErrorKind::InvalidOperand,
+                    span: span.clone(),
+                });
+            }
+            terms.push(Term::Or(
+                alternatives.into_iter().map(|(term, _)| term).collect(),
+            ));
+        }
+
+        Ok(SearchString { terms })
+    }
+}
+
+/// Parse a single term, including its negation, and return it together with
+/// its byte range.
+fn parse_term(
+    s: &str,
+    chars: &mut Peekable<CharIndices<'_>>,
+) -> Result<(Term, Range<usize>), ParseError> {
+    let start = chars.peek().map_or(s.len(), |&(i, _)| i);
+    let negated = chars.next_if(|&(_, c)| c == '-').is_some();
+
+    let prefix = chars
+        .next_if(|(_, c)| matches!(c, '!' | '#' | '+' | '*' | '>' | '<'))
+        .map(|(_, c)| c);
+    let value_start = chars.peek().map_or(s.len(), |&(i, _)| i);
+
+    let mut value = String::new();
+    let mut has_value = false;
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() || c == '|' {
+            break;
+        }
+        chars.next();
+        has_value = true;
+        match c {
+            '\\' => match chars.next() {
+                Some((_, c)) => value.push(c),
+                None => {
+                    return Err(ParseError {
+                        kind: ParseErrorKind::TrailingEscape,
+                        span: i..s.len(),
+                    })
+                }
+            },
+            '"' => loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => value.push(c),
+                        None => {
+                            return Err(ParseError {
+                                kind: ParseErrorKind::UnterminatedQuote,
+                                span: i..s.len(),
+                            })
+                        }
+                    },
+                    Some((_, c)) => value.push(c),
+                    None => {
+                        return Err(ParseError {
+                            kind: ParseErrorKind::UnterminatedQuote,
+                            span: i..s.len(),
+                        })
+                    }
+                }
+            },
+            ',' if prefix.is_some() => value.push(' '),
+            c => value.push(c),
+        }
+    }
+    let end = chars.peek().map_or(s.len(), |&(i, _)| i);
+
+    let term = match prefix {
+        _ if !has_value => {
+            return Err(ParseError {
+                kind: ParseErrorKind::EmptyValue,
+                span: start..end,
+            })
+        }
+        Some(prefix @ ('>' | '<')) => {
+            if negated {
+                return Err(ParseError {
+                    kind: ParseErrorKind::InvalidOperand,
+                    span: start..end,
+                });
+            }
+            let minutes = s[value_start..end]
+                .parse::<u64>()
+                .ok()
+                .and_then(|minutes| minutes.checked_mul(60))
+                .ok_or(ParseError {
+                    kind: ParseErrorKind::InvalidDuration,
+                    span: value_start..end,
+                })?;
+            let duration = Duration::from_secs(minutes);
+            if prefix == '>' {
+                Term::DurationMin(duration)
+            } else {
+                Term::DurationMax(duration)
+            }
+        }
+        Some('!') => Term::Channel(value),
+        Some('#') => Term::Topic(value),
+        Some('+') => Term::Title(value),
+        Some('*') => Term::Description(value),
+        _ => Term::Text(value),
+    };
+    let term = if negated {
+        Term::Not(Box::new(term))
+    } else {
+        term
+    };
+    Ok((term, start..end))
+}
+
+impl Display for SearchString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, term) in self.terms.iter().enumerate() {
+            if i > 0 {
+                f.write_char(' ')?;
+            }
+            term.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Term::Channel(value) => write_field_value(f, '!', value),
+            Term::Topic(value) => write_field_value(f, '#', value),
+            Term::Title(value) => write_field_value(f, '+', value),
+            Term::Description(value) => write_field_value(f, '*', value),
+            Term::DurationMin(duration) => write!(f, ">{}", duration.as_secs() / 60),
+            Term::DurationMax(duration) => write!(f, "<{}", duration.as_secs() / 60),
+            Term::Text(value) => write_text(f, value),
+            Term::Not(term) => write!(f, "-{term}"),
+            Term::Or(terms) => {
+                for (i, term) in terms.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" | ")?;
+                    }
+                    term.fmt(f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Write a field value, using commas instead of spaces.
+fn write_field_value(
+    f: &mut std::fmt::Formatter<'_>,
+    prefix: char,
+    value: &str,
+) -> std::fmt::Result {
+    f.write_char(prefix)?;
+    if value.is_empty() {
+        return f.write_str("\"\"");
+    }
+    for c in value.chars() {
+        match c {
+            ' ' => f.write_char(',')?,
+            ',' | '"' | '\\' | '|' => write!(f, "\\{c}")?,
+            c if c.is_whitespace() => write!(f, "\\{c}")?,
+            c => f.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write free text, quoting it if it contains whitespace.
+fn write_text(f: &mut std::fmt::Formatter<'_>, value: &str) -> std::fmt::Result {
+    let quote = value.is_empty() || value.contains(char::is_whitespace);
+    if quote {
+        f.write_char('"')?;
+    } else if value.starts_with(['-', '!', '#', '+', '*', '>', '<']) {
+        f.write_char('\\')?;
+    }
+    for c in value.chars() {
+        if matches!(c, '"' | '\\') || (c == '|' && !quote) {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    if quote {
+        f.write_char('"')?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ParseError, ParseErrorKind, SearchString, Term};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "!ard #sturm,der,liebe +\"rote rosen\" *a\\,b >30 <90 foo \"bar baz\""
+                .parse::<SearchString>()
+                .unwrap()
+                .terms,
+            [
+                Term::Channel("ard".into()),
+                Term::Topic("sturm der liebe".into()),
+                Term::Title("rote rosen".into()),
+                Term::Description("a,b".into()),
+                Term::DurationMin(Duration::from_secs(30 * 60)),
+                Term::DurationMax(Duration::from_secs(90 * 60)),
+                Term::Text("foo".into()),
+                Term::Text("bar baz".into()),
+            ]
+        );
+        assert_eq!(
+            "\\!ard #\"a\\\"b\"".parse::<SearchString>().unwrap().terms,
+            [Term::Text("!ard".into()), Term::Topic("a\"b".into())]
+        );
+        assert_eq!(
+            "  ".parse::<SearchString>().unwrap(),
+            SearchString::default()
+        );
+    }
+
+    #[test]
+    fn test_parse_operators() {
+        assert_eq!(
+            "-!ard -\"two words\" a-b \\-c !ard | !zdf #a|+b|\"c | d\" e\\|f -#g h"
+                .parse::<SearchString>()
+                .unwrap()
+                .terms,
+            [
+                Term::Not(Box::new(Term::Channel("ard".into()))),
+                Term::Not(Box::new(Term::Text("two words".into()))),
+                Term::Text("a-b".into()),
+                Term::Text("-c".into()),
+                Term::Or(vec![
+                    Term::Channel("ard".into()),
+                    Term::Channel("zdf".into())
+                ]),
+                Term::Or(vec![
+                    Term::Topic("a".into()),
+                    Term::Title("b".into()),
+                    Term::Text("c | d".into())
+                ]),
+                Term::Text("e|f".into()),
+                Term::Not(Box::new(Term::Topic("g".into()))),
+                Term::Text("h".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            "foo >abc".parse::<SearchString>(),
+            Err(ParseError {
+                kind: ParseErrorKind::InvalidDuration,
+                span: 5..8
+            })
+        );
+        assert_eq!(
+            "foo ! bar".parse::<SearchString>(),
+            Err(ParseError {
+                kind: ParseErrorKind::EmptyValue,
+                span: 4..5
+            })
+        );
+        assert_eq!(
+            "#\"foo bar".parse::<SearchString>(),
+            Err(ParseError {
+                kind: ParseErrorKind::UnterminatedQuote,
+                span: 1..9
+            })
+        );
+        assert_eq!(
+            "foo\\".parse::<SearchString>(),
+            Err(ParseError {
+                kind: ParseErrorKind::TrailingEscape,
+                span: 3..4
+            })
+        );
+        assert_eq!(
+            "foo - bar".parse::<SearchString>(),
+            Err(ParseError {
+                kind: ParseErrorKind::EmptyValue,
+                span: 4..5
+            })
+        );
+        assert_eq!(
+            "| foo".parse::<SearchString>(),
+            Err(ParseError {
+                kind: ParseErrorKind::MissingOperand,
+                span: 0..1
+            })
+        );
+        assert_eq!(
+            "foo | | bar".parse::<SearchString>(),
+            Err(ParseError {
+                kind: ParseErrorKind::MissingOperand,
+                span: 4..5
+            })
+        );
+        assert_eq!(
+            "foo |".parse::<SearchString>(),
+            Err(ParseError {
+                kind: ParseErrorKind::MissingOperand,
+                span: 4..5
+            })
+        );
+        assert_eq!(
+            "foo ->30".parse::<SearchString>(),
+            Err(ParseError {
+                kind: ParseErrorKind::InvalidOperand,
+                span: 4..8
+            })
+        );
+        assert_eq!(
+            "foo | -bar".parse::<SearchString>(),
+            Err(ParseError {
+                kind: ParseErrorKind::InvalidOperand,
+                span: 6..10
+            })
+        );
+        assert_eq!(
+            "<30|>10".parse::<SearchString>(),
+            Err(ParseError {
+                kind: ParseErrorKind::InvalidOperand,
+                span: 0..3
+            })
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let search = SearchString {
+            terms: vec![
+                Term::Channel("ard".into()),
+                Term::Topic("sturm der liebe".into()),
+                Term::Title("a,b \"c\"".into()),
+                Term::Description(String::new()),
+                Term::DurationMin(Duration::from_secs(30 * 60)),
+                Term::DurationMax(Duration::from_secs(90 * 60)),
+                Term::Text("#hashtag".into()),
+                Term::Text("two words".into()),
+                Term::Text("back\\slash".into()),
+                Term::Text("-dash".into()),
+                Term::Text("pi|pe".into()),
+                Term::Not(Box::new(Term::Topic("wet|ter".into()))),
+                Term::Or(vec![Term::Text("a b".into()), Term::Channel("zdf".into())]),
+            ],
+        };
+        assert_eq!(
+            search.to_string(),
+            r#"!ard #sturm,der,liebe +a\,b,\"c\" *"" >30 <90 \#hashtag "two words" back\\slash \-dash pi\|pe -#wet\|ter "a b" | !zdf"#
+        );
+        assert_eq!(search.to_string().parse::<SearchString>(), Ok(search));
+    }
+}
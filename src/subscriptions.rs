@@ -0,0 +1,434 @@
+//! Saved searches that report new items, like the subscriptions ("Abos") of
+//! MediathekView.
+//!
+//! A [`SubscriptionStore`] keeps a list of [`Subscription`]s and the IDs of
+//! the items that each subscription has already returned in a JSON file.
+//! [`SubscriptionStore::check`] sends a subscription's query and returns only
+//! the items that haven't been seen before.
+//!
+//! Subscriptions are checked newest items first, retrieving further pages
+//! until a page doesn't contain any new items, but at most
+//! [`SubscriptionStore::CHECK_LIMIT`] items. Only the
+//! [`SubscriptionStore::MAX_SEEN`] newest seen items of each subscription are
+//! remembered.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example(mediathek: mediathekviewweb::Mediathek) -> mediathekviewweb::Result<()> {
+//! use std::time::Duration;
+//!
+//! use mediathekviewweb::{
+//!     models::MediathekQuery,
+//!     subscriptions::{Subscription, SubscriptionStore},
+//! };
+//!
+//! let mut store = SubscriptionStore::open("subscriptions.json")?;
+//! store.add(
+//!     Subscription::new(
+//!         "Die Sendung mit der Maus",
+//!         MediathekQuery::from_search_string("#sendung,mit,der,maus", false)?,
+//!     )
+//!     .min_duration(Duration::from_secs(20 * 60))
+//!     .target_dir("videos/maus"),
+//! );
+//!
+//! for (name, items) in store.check_all(&mediathek).await? {
+//!     for item in items {
+//!         println!("{name}: {}", item.title);
+//!     }
+//! }
+//! store.save()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{Item, MediathekQuery, SortField, SortOrder},
+    Mediathek,
+};
+
+/// A saved search.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Subscription {
+    /// The unique name of the subscription.
+    pub name: String,
+    pub query: MediathekQuery,
+    /// Items that are shorter than this are ignored. Items without a duration,
+    /// e.g. livestreams, are always included.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::models::optional_duration_secs"
+    )]
+    pub min_duration: Option<Duration>,
+    /// The directory in which the videos of this subscription should be
+    /// saved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_dir: Option<PathBuf>,
+}
+impl Subscription {
+    pub fn new(name: impl Into<String>, query: MediathekQuery) -> Self {
+        Self {
+            name: name.into(),
+            query,
+            min_duration: None,
+            target_dir: None,
+        }
+    }
+    /// Ignore items that are shorter than `min_duration`.
+    pub fn min_duration(mut self, min_duration: Duration) -> Self {
+        self.min_duration = Some(min_duration);
+        self
+    }
+    /// Set the directory in which the videos should be saved.
+    pub fn target_dir(mut self, target_dir: impl Into<PathBuf>) -> Self {
+        self.target_dir = Some(target_dir.into());
+        self
+    }
+
+    /// Check whether an item matches the filters of this subscription that
+    /// aren't part of the query.
+    fn accepts(&self, item: &Item) -> bool {
+        match (self.min_duration, item.duration) {
+            (Some(min_duration), Some(duration)) => duration >= min_duration,
+            _ => true,
+        }
+    }
+}
+
+/// A list of [`Subscription`]s and the items they have already returned,
+/// persisted as a JSON file.
+#[derive(Clone, Debug)]
+pub struct SubscriptionStore {
+    path: PathBuf,
+    state: StoreFile,
+}
+/// The contents of a store file.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct StoreFile {
+    subscriptions: Vec<Subscription>,
+    /// The IDs and timestamps of the items that have been seen, by
+    /// subscription name.
+    seen: BTreeMap<String, Seen>,
+}
+type Seen = BTreeMap<String, i64>;
+impl SubscriptionStore {
+    /// The maximum count of items that are retrieved when checking a
+    /// subscription.
+    pub const CHECK_LIMIT: usize = 500;
+    /// The count of seen items that are remembered for each subscription.
+    /// Older items are forgotten.
+    pub const MAX_SEEN: usize = 5000;
+
+    /// Load a store from `path`. If the file doesn't exist, an empty store is
+    /// created, which is written to `path` by [`save`](Self::save).
+    pub fn open(path: impl Into<PathBuf>) -> crate::Result<Self> {
+        let path = path.into();
+        let state = match std::fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => StoreFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, state })
+    }
+
+    /// The path of the store file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write the store to its file.
+    pub fn save(&self) -> crate::Result<()> {
+        let contents = serde_json::to_vec_pretty(&self.state)?;
+        // write to a temporary file first so the store is never left corrupted
+        let mut temp = self.path.as_os_str().to_owned();
+        temp.push(".tmp");
+        std::fs::write(&temp, contents)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn subscriptions(&self) -> &[Subscription] {
+        &self.state.subscriptions
+    }
+
+    /// Return the subscription with the specified name.
+    pub fn get(&self, name: &str) -> Option<&Subscription> {
+        self.state.subscriptions.iter().find(|s| s.name == name)
+    }
+
+    /// Add a subscription.
+    ///
+    /// If there already is a subscription with the same name, it is replaced
+    /// and returned. Its seen items are kept.
+    pub fn add(&mut self, subscription: Subscription) -> Option<Subscription> {
+        match self
+            .state
+            .subscriptions
+            .iter_mut()
+            .find(|s| s.name == subscription.name)
+        {
+            Some(existing) => Some(std::mem::replace(existing, subscription)),
+            None => {
+                self.state.subscriptions.push(subscription);
+                None
+            }
+        }
+    }
+
+    /// Remove a subscription and forget its seen items.
+    pub fn remove(&mut self, name: &str) -> Option<Subscription> {
+        let index = self
+            .state
+            .subscriptions
+            .iter()
+            .position(|s| s.name == name)?;
+        self.state.seen.remove(name);
+        Some(self.state.subscriptions.remove(index))
+    }
+
+    /// Send the query of a subscription and return the items that haven't been
+    /// seen before.
+    ///
+    /// The results are sorted by their timestamp, newest first, replacing the
+    /// sort order of the query. Further pages are requested until a page
+    /// doesn't contain new items or [`CHECK_LIMIT`](Self::CHECK_LIMIT) items
+    /// have been retrieved.
+    ///
+    /// The returned items are marked as seen. Call [`save`](Self::save) to
+    /// persist them. Returns `Ok(None)` if there is no subscription with this
+    /// name.
+    pub async fn check(
+        &mut self,
+        mediathek: &Mediathek,
+        name: &str,
+    ) -> crate::Result<Option<Vec<Item>>> {
+        let Some(subscription) = self.get(name) else {
+            return Ok(None);
+        };
+        let items = fetch(mediathek, subscription, self.state.seen.get(name)).await?;
+        Ok(Some(self.filter_new(name, items)))
+    }
+
+    /// [`check`](Self::check) all subscriptions and return their new items by
+    /// subscription name.
+    ///
+    /// The items are only marked as seen if all requests succeed.
+    pub async fn check_all(
+        &mut self,
+        mediathek: &Mediathek,
+    ) -> crate::Result<Vec<(String, Vec<Item>)>> {
+        let mut results = Vec::with_capacity(self.state.subscriptions.len());
+        for subscription in &self.state.subscriptions {
+            let seen = self.state.seen.get(&subscription.name);
+            let items = fetch(mediathek, subscription, seen).await?;
+            results.push((subscription.name.clone(), items));
+        }
+        Ok(results
+            .into_iter()
+            .map(|(name, items)| {
+                let items = self.filter_new(&name, items);
+                (name, items)
+            })
+            .collect())
+    }
+
+    /// Return the items that match the filters of a subscription and haven't
+    /// been seen before, and mark them as seen.
+    ///
+    /// This can be used to check subscriptions using the results of another
+    /// client, e.g. the blocking client. Returns an empty
+    /// list if there is no subscription with this name.
+    pub fn filter_new(&mut self, name: &str, items: impl IntoIterator<Item = Item>) -> Vec<Item> {
+        let Some(subscription) = self.state.subscriptions.iter().find(|s| s.name == name) else {
+            return Vec::new();
+        };
+        let seen = self.state.seen.entry(name.to_owned()).or_default();
+        let new = items
+            .into_iter()
+            .filter(|item| {
+                subscription.accepts(item) && seen.insert(item.id.clone(), item.timestamp).is_none()
+            })
+            .collect();
+
+        // forget the oldest items
+        if seen.len() > Self::MAX_SEEN {
+            let mut timestamps = seen.values().copied().collect::<Vec<_>>();
+            timestamps.sort_unstable();
+            let oldest = timestamps[timestamps.len() - Self::MAX_SEEN];
+            seen.retain(|_, timestamp| *timestamp >= oldest);
+        }
+        new
+    }
+}
+
+/// Send the query of a subscription, newest items first, until a page only
+/// contains items that have been seen before or don't match the subscription's
+/// filters.
+async fn fetch(
+    mediathek: &Mediathek,
+    subscription: &Subscription,
+    seen: Option<&Seen>,
+) -> crate::Result<Vec<Item>> {
+    let query = MediathekQuery {
+        sort_by: Some(SortField::Timestamp),
+        sort_order: Some(SortOrder::Descending),
+        ..subscription.query.clone()
+    };
+    let pages = mediathek
+        .execute(query)
+        .limit(SubscriptionStore::CHECK_LIMIT)
+        .pages();
+    let mut pages = std::pin::pin!(pages);
+    let mut items = Vec::new();
+    while let Some(page) = pages.next().await {
+        let page = page?.results;
+        let done = page.iter().all(|item| {
+            !subscription.accepts(item) || seen.is_some_and(|seen| seen.contains_key(&item.id))
+        });
+        items.extend(page);
+        if done {
+            break;
+        }
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use super::{Subscription, SubscriptionStore};
+    use crate::{mock::fixtures, models::MediathekQuery};
+
+    fn store_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "mediathekviewweb-subscriptions-{name}-{}.json",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_subscription_store() -> crate::Result<()> {
+        let path = store_path("store");
+        let _ = std::fs::remove_file(&path);
+
+        let mut store = SubscriptionStore::open(&path)?;
+        assert!(store.subscriptions().is_empty());
+        let subscription = Subscription::new(
+            "tagesschau",
+            MediathekQuery::from_search_string("#tagesschau", false)?,
+        )
+        .min_duration(Duration::from_secs(15 * 60))
+        .target_dir("videos");
+        assert_eq!(store.add(subscription.clone()), None);
+
+        let items = fixtures();
+        let long = items
+            .iter()
+            .filter(|item| {
+                item.duration
+                    .is_some_and(|d| d >= Duration::from_secs(15 * 60))
+            })
+            .count();
+        let unknown = items.iter().filter(|item| item.duration.is_none()).count();
+        let new = store.filter_new("tagesschau", items.clone());
+        assert_eq!(new.len(), long + unknown);
+        assert!(store.filter_new("tagesschau", items.clone()).is_empty());
+        assert!(store.filter_new("unknown", items.clone()).is_empty());
+        store.save()?;
+
+        // the seen items are persisted
+        let mut store = SubscriptionStore::open(&path)?;
+        assert_eq!(store.subscriptions(), std::slice::from_ref(&subscription));
+        assert!(store.filter_new("tagesschau", items.clone()).is_empty());
+
+        // replacing a subscription keeps its seen items, removing forgets them
+        assert_eq!(
+            store.add(subscription.clone().target_dir("other")),
+            Some(subscription.clone())
+        );
+        assert!(store.filter_new("tagesschau", items.clone()).is_empty());
+        assert!(store.remove("tagesschau").is_some());
+        store.add(subscription);
+        assert_eq!(store.filter_new("tagesschau", items).len(), long + unknown);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_check() -> Result<(), Box<dyn std::error::Error>> {
+        let server = crate::mock::MockServer::start()?;
+        let mediathek = crate::Mediathek::new_with_url(server.url(), "test".parse().unwrap())?;
+        let path = store_path("check");
+        let _ = std::fs::remove_file(&path);
+
+        let mut store = SubscriptionStore::open(&path)?;
+        store.add(Subscription::new(
+            "tagesschau",
+            MediathekQuery::from_search_string("#tagesschau >12", false)?,
+        ));
+        store.add(Subscription::new(
+            "livestreams",
+            MediathekQuery::from_search_string("#livestream", false)?,
+        ));
+
+        let new_items = store.check_all(&mediathek).await?;
+        assert_eq!(
+            new_items
+                .iter()
+                .map(|(name, items)| (name.as_str(), items.len()))
+                .collect::<Vec<_>>(),
+            [("tagesschau", 3), ("livestreams", 2)]
+        );
+        assert_eq!(store.check(&mediathek, "tagesschau").await?, Some(vec![]));
+        assert_eq!(store.check(&mediathek, "unknown").await?, None);
+
+        // all pages are checked until a page only contains seen items
+        store.add(Subscription::new(
+            "all",
+            MediathekQuery {
+                size: Some(3),
+                ..MediathekQuery::default()
+            },
+        ));
+        let items = store.check(&mediathek, "all").await?.unwrap();
+        assert_eq!(items.len(), fixtures().len());
+        assert!(items
+            .windows(2)
+            .all(|items| items[0].timestamp >= items[1].timestamp));
+        let requests = server.request_count();
+        assert_eq!(store.check(&mediathek, "all").await?, Some(vec![]));
+        assert_eq!(server.request_count(), requests + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_seen() {
+        let mut store = SubscriptionStore::open(store_path("max-seen")).unwrap();
+        store.add(Subscription::new("all", MediathekQuery::default()));
+        let items = (0..=SubscriptionStore::MAX_SEEN as i64)
+            .map(|i| crate::models::Item {
+                id: i.to_string(),
+                timestamp: i,
+                ..fixtures().remove(0)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(store.filter_new("all", items.clone()).len(), items.len());
+
+        // the oldest item has been forgotten
+        assert_eq!(store.filter_new("all", items[..2].to_vec()), &items[..1]);
+        assert!(store.filter_new("all", items[2..].to_vec()).is_empty());
+    }
+}
@@ -0,0 +1,1128 @@
+//! Support for the subtitles referenced by [`Item::url_subtitle`].
+//!
+//! ARD and ZDF provide subtitles as [TTML](https://www.w3.org/TR/ttml1/)
+//! documents, mostly using the [EBU-TT-D](https://tech.ebu.ch/publications/tech3380)
+//! profile. [`Subtitles`] parses these documents into a list of [`Cue`]s and
+//! converts them to SRT or WebVTT, e.g. to mux them with a downloaded video.
+//!
+//! Speaker changes are usually indicated by different text colours, which are
+//! preserved as the [`Color`] of each [`Span`].
+//!
+//! A [`SubtitleIndex`] searches the subtitles of multiple items for a phrase
+//! and returns the cues in which it was said.
+//!
+//! # Example
+//! ```rust
+//! use mediathekviewweb::subtitles::Subtitles;
+//!
+//! let subtitles: Subtitles = r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling">
+//!   <body>
+//!     <div>
+//!       <p begin="00:00:01.000" end="00:00:03.500">Guten Abend<br/><span tts:color="yellow">und willkommen.</span></p>
+//!     </div>
+//!   </body>
+//! </tt>"#
+//!     .parse()
+//!     .unwrap();
+//!
+//! assert_eq!(
+//!     subtitles.to_srt(),
+//!     "1\n00:00:01,000 --> 00:00:03,500\nGuten Abend\n<font color=\"#ffff00\">und willkommen.</font>\n\n"
+//! );
+//! ```
+
+use std::{collections::HashMap, fmt::Display, str::FromStr, time::Duration};
+
+use quick_xml::{events::BytesStart, events::Event, Reader, XmlVersion};
+
+use crate::models::Item;
+
+/// An error caused by a malformed subtitle document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubtitleError {
+    /// The byte offset in the document where the error occured.
+    pub offset: u64,
+    /// A description of the error.
+    pub message: String,
+}
+impl Display for SubtitleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+impl std::error::Error for SubtitleError {}
+
+/// Parsed subtitles.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Subtitles {
+    /// The cues in the order of the document.
+    pub cues: Vec<Cue>,
+}
+impl Subtitles {
+    /// Download and parse subtitles, e.g. from [`Item::url_subtitle`].
+    #[cfg(feature = "reqwest")]
+    pub async fn fetch(http: &reqwest::Client, url: &str) -> crate::Result<Self> {
+        let response = http.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(crate::Error::Status(response.status().as_u16()));
+        }
+        Ok(response.text().await?.parse()?)
+    }
+
+    /// Show all cues later by the specified duration.
+    pub fn delay(&mut self, offset: Duration) {
+        for cue in &mut self.cues {
+            cue.start += offset;
+            cue.end += offset;
+        }
+    }
+
+    /// Show all cues earlier by the specified duration.
+    ///
+    /// ARD's subtitles often use the timecodes of the broadcast, which start at
+    /// `10:00:00`, so they have to be advanced by 10 hours to match the
+    /// video. Cues that would start before the video are clamped to zero.
+    pub fn advance(&mut self, offset: Duration) {
+        for cue in &mut self.cues {
+            cue.start = cue.start.saturating_sub(offset);
+            cue.end = cue.end.saturating_sub(offset);
+        }
+    }
+
+    /// Convert the subtitles to SubRip (SRT).
+    ///
+    /// Coloured text (except white, the usual default) is wrapped in
+    /// `<font color>` tags. `&`, `<` and `>` are escaped, so they aren't
+    /// mistaken for markup.
+    pub fn to_srt(&self) -> String {
+        let mut srt = String::new();
+        for (i, cue) in self.cues.iter().enumerate() {
+            srt += &format!(
+                "{}\n{} --> {}\n",
+                i + 1,
+                timestamp(cue.start, ','),
+                timestamp(cue.end, ',')
+            );
+            for span in &cue.spans {
+                let text = escape(&span.text);
+                match span.color.filter(|&color| color != Color::WHITE) {
+                    Some(color) => srt += &format!("<font color=\"{color}\">{text}</font>"),
+                    None => srt += &text,
+                }
+            }
+            srt += "\n\n";
+        }
+        srt
+    }
+
+    /// Convert the subtitles to WebVTT.
+    ///
+    /// Text in one of WebVTT's default colours (except white) is wrapped in a
+    /// class span like `<c.yellow>`. Other colours are dropped because they
+    /// would require a style sheet.
+    pub fn to_webvtt(&self) -> String {
+        let mut vtt = String::from("WEBVTT\n\n");
+        for (i, cue) in self.cues.iter().enumerate() {
+            vtt += &format!(
+                "{}\n{} --> {}\n",
+                i + 1,
+                timestamp(cue.start, '.'),
+                timestamp(cue.end, '.')
+            );
+            for span in &cue.spans {
+                let text = escape(&span.text);
+                match span.color.and_then(Color::webvtt_class) {
+                    Some(class) => vtt += &format!("<c.{class}>{text}</c>"),
+                    None => vtt += &text,
+                }
+            }
+            vtt += "\n\n";
+        }
+        vtt
+    }
+}
+/// Escapes the characters of a text that would be interpreted as markup by
+/// SRT and WebVTT players.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl FromStr for Subtitles {
+    type Err = SubtitleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Parser::new(s).parse()
+    }
+}
+
+/// A piece of text that is shown for a period of time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    /// The text of the cue, split into differently styled parts.
+    pub spans: Vec<Span>,
+}
+impl Cue {
+    /// The text of the cue without styling. Lines are separated by `\n`.
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+}
+
+/// A part of a [`Cue`]'s text with the same styling.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    /// The text, possibly containing line breaks (`\n`).
+    pub text: String,
+    /// The text colour, if the document specifies one.
+    pub color: Option<Color>,
+}
+
+/// An RGBA colour.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+impl Color {
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const RED: Color = Color::rgb(255, 0, 0);
+    pub const LIME: Color = Color::rgb(0, 255, 0);
+    pub const BLUE: Color = Color::rgb(0, 0, 255);
+    pub const YELLOW: Color = Color::rgb(255, 255, 0);
+    pub const CYAN: Color = Color::rgb(0, 255, 255);
+    pub const MAGENTA: Color = Color::rgb(255, 0, 255);
+
+    /// Create an opaque colour.
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Color {
+            red,
+            green,
+            blue,
+            alpha: 255,
+        }
+    }
+
+    /// Parse a TTML colour, e.g. `#ffff00`, `#ffff00c2`, `rgb(255,255,0)` or
+    /// `yellow`.
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            let component = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+            return match hex.len() {
+                6 => Some(Color::rgb(component(0)?, component(2)?, component(4)?)),
+                8 => Some(Color {
+                    alpha: component(6)?,
+                    ..Color::rgb(component(0)?, component(2)?, component(4)?)
+                }),
+                _ => None,
+            };
+        }
+        if let Some(components) = s.strip_prefix("rgba(").or_else(|| s.strip_prefix("rgb(")) {
+            let components = components
+                .strip_suffix(')')?
+                .split(',')
+                .map(|component| component.trim().parse())
+                .collect::<Result<Vec<u8>, _>>()
+                .ok()?;
+            return match components[..] {
+                [red, green, blue] => Some(Color::rgb(red, green, blue)),
+                [red, green, blue, alpha] => Some(Color {
+                    red,
+                    green,
+                    blue,
+                    alpha,
+                }),
+                _ => None,
+            };
+        }
+        Some(match s.to_ascii_lowercase().as_str() {
+            "white" => Color::WHITE,
+            "black" => Color::BLACK,
+            "red" => Color::RED,
+            "lime" => Color::LIME,
+            "green" => Color::rgb(0, 128, 0),
+            "blue" => Color::BLUE,
+            "yellow" => Color::YELLOW,
+            "cyan" | "aqua" => Color::CYAN,
+            "magenta" | "fuchsia" => Color::MAGENTA,
+            "gray" | "grey" => Color::rgb(128, 128, 128),
+            "silver" => Color::rgb(192, 192, 192),
+            "transparent" => Color {
+                alpha: 0,
+                ..Color::BLACK
+            },
+            _ => return None,
+        })
+    }
+
+    /// The name of the WebVTT default class for this colour (except white).
+    fn webvtt_class(self) -> Option<&'static str> {
+        let opaque = Color { alpha: 255, ..self };
+        Some(match opaque {
+            Color::BLACK => "black",
+            Color::RED => "red",
+            Color::LIME => "lime",
+            Color::BLUE => "blue",
+            Color::YELLOW => "yellow",
+            Color::CYAN => "cyan",
+            Color::MAGENTA => "magenta",
+            _ => return None,
+        })
+    }
+}
+impl Display for Color {
+    /// Format the colour as `#rrggbb`, or `#rrggbbaa` if it isn't opaque.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)?;
+        if self.alpha != 255 {
+            write!(f, "{:02x}", self.alpha)?;
+        }
+        Ok(())
+    }
+}
+
+/// Format a timestamp as `HH:MM:SS,mmm` with the specified decimal separator.
+fn timestamp(time: Duration, separator: char) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// The inherited properties of an element.
+#[derive(Copy, Clone, Debug, Default)]
+struct Scope {
+    color: Option<Color>,
+    /// The begin of the parent's time interval.
+    begin: Duration,
+}
+
+struct Parser<'a> {
+    reader: Reader<&'a [u8]>,
+    /// The colours of the styles defined in the document's head.
+    styles: HashMap<String, Option<Color>>,
+    frame_rate: Option<f64>,
+    frame_rate_multiplier: f64,
+    tick_rate: Option<f64>,
+    scopes: Vec<Scope>,
+    /// The cue of the `<p>` element that is currently parsed.
+    cue: Option<Cue>,
+    cues: Vec<Cue>,
+}
+impl<'a> Parser<'a> {
+    fn new(xml: &'a str) -> Self {
+        Parser {
+            reader: Reader::from_str(xml),
+            styles: HashMap::new(),
+            frame_rate: None,
+            frame_rate_multiplier: 1.0,
+            tick_rate: None,
+            scopes: Vec::new(),
+            cue: None,
+            cues: Vec::new(),
+        }
+    }
+
+    fn parse(mut self) -> Result<Subtitles, SubtitleError> {
+        loop {
+            let event = self.reader.read_event().map_err(|e| SubtitleError {
+                offset: self.reader.error_position(),
+                message: e.to_string(),
+            })?;
+            match event {
+                Event::Start(element) => {
+                    let scope = self.start(&element)?;
+                    self.scopes.push(scope);
+                }
+                Event::Empty(element) => {
+                    self.start(&element)?;
+                    self.end(element.local_name().as_ref());
+                }
+                Event::End(element) => {
+                    self.scopes.pop();
+                    self.end(element.local_name().as_ref());
+                }
+                Event::Text(text) => self.push_text(&text.xml10_content()),
+                Event::CData(text) => self.push_text(&text.xml10_content()),
+                Event::GeneralRef(reference) => {
+                    let resolved = match reference.resolve_char_ref() {
+                        Ok(Some(c)) => c.to_string(),
+                        Ok(None) => quick_xml::escape::resolve_predefined_entity(&reference)
+                            .ok_or_else(|| self.error(format!("unknown entity `{}`", &*reference)))?
+                            .to_owned(),
+                        Err(e) => return Err(self.error(e.to_string())),
+                    };
+                    // don't collapse resolved whitespace like `&#10;`
+                    if let Some(cue) = &mut self.cue {
+                        push_span(cue, &resolved, self.scopes.last().and_then(|s| s.color));
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(Subtitles { cues: self.cues })
+    }
+
+    /// Process a start tag and return the scope of its content.
+    fn start(&mut self, element: &BytesStart) -> Result<Scope, SubtitleError> {
+        let parent = self.scopes.last().copied().unwrap_or_default();
+        let mut scope = parent;
+        let mut id = None;
+        let mut styles = Vec::new();
+        let mut color = None;
+        let mut begin = None;
+        let mut end = None;
+        let mut dur = None;
+        for attribute in element.attributes() {
+            let attribute = attribute.map_err(|e| self.error(e.to_string()))?;
+            let value = attribute
+                .normalized_value(XmlVersion::Implicit1_0)
+                .map_err(|e| self.error(e.to_string()))?;
+            match attribute.key.local_name().as_ref() {
+                "id" => id = Some(value.into_owned()),
+                "style" => styles = value.split_whitespace().map(str::to_owned).collect(),
+                "color" => {
+                    color = Some(
+                        Color::parse(&value)
+                            .ok_or_else(|| self.error(format!("invalid colour `{value}`")))?,
+                    )
+                }
+                "frameRate" => self.frame_rate = Some(self.parse_number(&value)?),
+                "frameRateMultiplier" => {
+                    let (numerator, denominator) = value
+                        .split_once(' ')
+                        .ok_or_else(|| self.error("invalid frame rate multiplier"))?;
+                    self.frame_rate_multiplier =
+                        self.parse_number(numerator)? / self.parse_number(denominator)?
+                }
+                "tickRate" => self.tick_rate = Some(self.parse_number(&value)?),
+                "begin" => begin = Some(self.parse_time(&value)?),
+                "end" => end = Some(self.parse_time(&value)?),
+                "dur" => dur = Some(self.parse_time(&value)?),
+                _ => {}
+            }
+        }
+
+        // inline styling takes precedence over referenced styles
+        for style in &styles {
+            if let Some(Some(style_color)) = self.styles.get(style) {
+                scope.color = Some(*style_color);
+            }
+        }
+        scope.color = color.or(scope.color);
+
+        match element.local_name().as_ref() {
+            "style" => {
+                if let Some(id) = id {
+                    self.styles.insert(id, scope.color);
+                }
+            }
+            "p" => {
+                let begin = begin.ok_or_else(|| self.error("missing `begin` attribute"))?;
+                let end = match (end, dur) {
+                    (Some(end), _) => end,
+                    (None, Some(dur)) => begin + dur,
+                    (None, None) => return Err(self.error("missing `end` attribute")),
+                };
+                self.cue = Some(Cue {
+                    start: parent.begin + begin,
+                    end: parent.begin + end,
+                    spans: Vec::new(),
+                });
+            }
+            "br" => {
+                if let Some(cue) = &mut self.cue {
+                    push_span(cue, "\n", scope.color);
+                }
+            }
+            // time containers, e.g. `<div begin="…">`
+            _ => scope.begin += begin.unwrap_or_default(),
+        }
+        Ok(scope)
+    }
+
+    fn end(&mut self, name: &str) {
+        if name == "p" {
+            if let Some(cue) = self.cue.take().and_then(normalize_whitespace) {
+                self.cues.push(cue);
+            }
+        }
+    }
+
+    /// Add text to the current cue, replacing XML whitespace by spaces.
+    fn push_text(&mut self, text: &str) {
+        if let Some(cue) = &mut self.cue {
+            let text = text.replace(['\t', '\r', '\n'], " ");
+            push_span(cue, &text, self.scopes.last().and_then(|s| s.color));
+        }
+    }
+
+    fn parse_number(&self, value: &str) -> Result<f64, SubtitleError> {
+        value
+            .trim()
+            .parse()
+            .ok()
+            .filter(|n: &f64| n.is_finite() && *n > 0.0)
+            .ok_or_else(|| self.error(format!("invalid number `{value}`")))
+    }
+
+    fn parse_time(&self, value: &str) -> Result<Duration, SubtitleError> {
+        // the defaults as specified by TTML: 30 frames per second and, unless
+        // a frame rate is set, one tick per second
+        let frame_rate = self.frame_rate.unwrap_or(30.0) * self.frame_rate_multiplier;
+        let tick_rate = self
+            .tick_rate
+            .or(self.frame_rate.map(|_| frame_rate))
+            .unwrap_or(1.0);
+        parse_time(value, frame_rate, tick_rate)
+            .ok_or_else(|| self.error(format!("invalid time expression `{value}`")))
+    }
+
+    fn error(&self, message: impl Into<String>) -> SubtitleError {
+        SubtitleError {
+            offset: self.reader.buffer_position(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Append text to the last span of a cue if it has the same colour.
+fn push_span(cue: &mut Cue, text: &str, color: Option<Color>) {
+    match cue.spans.last_mut() {
+        Some(span) if span.color == color => span.text += text,
+        _ => cue.spans.push(Span {
+            text: text.to_owned(),
+            color,
+        }),
+    }
+}
+
+/// Collapse consecutive spaces and remove spaces and empty lines at the
+/// beginning and end of lines.
+///
+/// Returns `None` if the cue doesn't contain any text.
+fn normalize_whitespace(mut cue: Cue) -> Option<Cue> {
+    let mut line_start = true;
+    let mut pending_space = false;
+    for span in &mut cue.spans {
+        let text = std::mem::take(&mut span.text);
+        for c in text.chars() {
+            match c {
+                ' ' => pending_space = !line_start,
+                '\n' => {
+                    if !line_start {
+                        span.text.push('\n');
+                    }
+                    line_start = true;
+                    pending_space = false;
+                }
+                c => {
+                    if pending_space {
+                        span.text.push(' ');
+                        pending_space = false;
+                    }
+                    span.text.push(c);
+                    line_start = false;
+                }
+            }
+        }
+    }
+    cue.spans.retain(|span| !span.text.is_empty());
+    if let Some(span) = cue.spans.last_mut() {
+        span.text.truncate(span.text.trim_end_matches('\n').len());
+    }
+    cue.spans.retain(|span| !span.text.is_empty());
+    // line breaks and whitespace between spans don't need their own colour
+    cue.spans.dedup_by(|span, previous| {
+        let merge = span.color == previous.color || span.text.trim().is_empty();
+        if merge {
+            previous.text += &span.text;
+        }
+        merge
+    });
+    (!cue.spans.is_empty()).then_some(cue)
+}
+
+/// Parse a TTML time expression, e.g. `00:00:01.200`, `00:00:01:05` (with
+/// frames), `1.2s` or `12000000t` (with ticks).
+fn parse_time(value: &str, frame_rate: f64, tick_rate: f64) -> Option<Duration> {
+    let value = value.trim();
+    let seconds = if value.contains(':') {
+        let (hours, minutes, seconds, frames) = match value.split(':').collect::<Vec<_>>()[..] {
+            [hours, minutes, seconds] => (hours, minutes, seconds, None),
+            [hours, minutes, seconds, frames] => (hours, minutes, seconds, Some(frames)),
+            _ => return None,
+        };
+        let hours: u64 = hours.parse().ok()?;
+        let minutes: u64 = minutes.parse().ok()?;
+        let seconds: f64 = seconds.parse().ok()?;
+        let frames: f64 = frames.map_or(Some(0.0), |frames| frames.parse().ok())?;
+        hours
+            .checked_mul(3600)?
+            .checked_add(minutes.checked_mul(60)?)? as f64
+            + seconds
+            + frames / frame_rate
+    } else {
+        let unit = value.find(|c: char| c.is_ascii_alphabetic())?;
+        let count: f64 = value[..unit].parse().ok()?;
+        count
+            * match &value[unit..] {
+                "h" => 3600.0,
+                "m" => 60.0,
+                "s" => 1.0,
+                "ms" => 0.001,
+                "f" => 1.0 / frame_rate,
+                "t" => 1.0 / tick_rate,
+                _ => return None,
+            }
+    };
+    // round to microseconds to avoid imprecise timestamps like 1.199999
+    let micros = (seconds * 1_000_000.0).round();
+    (micros.is_finite() && micros >= 0.0).then(|| Duration::from_micros(micros as u64))
+}
+
+/// ARD's subtitles often use the timecodes of the broadcast, which start at
+/// `10:00:00`.
+#[cfg(feature = "reqwest")]
+const BROADCAST_OFFSET: Duration = Duration::from_secs(10 * 3600);
+
+/// A full-text index of the subtitles of multiple items.
+///
+/// Searches are case-insensitive and ignore line breaks, so phrases are also
+/// found if they are split across lines or cues.
+#[derive(Clone, Debug, Default)]
+pub struct SubtitleIndex {
+    documents: Vec<Document>,
+}
+
+#[derive(Clone, Debug)]
+struct Document {
+    item: Item,
+    cues: Vec<Cue>,
+    /// The normalized text of all cues, separated by spaces.
+    text: String,
+    /// The offset of each cue's text in `text`.
+    offsets: Vec<usize>,
+}
+
+impl SubtitleIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The count of indexed items.
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Add the subtitles of an item to the index.
+    pub fn insert(&mut self, item: Item, subtitles: Subtitles) {
+        let mut text = String::new();
+        let mut offsets = Vec::with_capacity(subtitles.cues.len());
+        for cue in &subtitles.cues {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            offsets.push(text.len());
+            text += &normalize(&cue.text());
+        }
+        self.documents.push(Document {
+            item,
+            cues: subtitles.cues,
+            text,
+            offsets,
+        });
+    }
+
+    /// Download the subtitles of an item and add them to the index.
+    ///
+    /// Items without subtitles are skipped. Subtitles that start at the
+    /// broadcast timecode `10:00:00` are moved to the start of the video.
+    #[cfg(feature = "reqwest")]
+    pub async fn fetch(&mut self, http: &reqwest::Client, item: Item) -> crate::Result<()> {
+        if let Some(subtitles) = fetch_subtitles(http, &item).await? {
+            self.insert(item, subtitles);
+        }
+        Ok(())
+    }
+
+    /// Download the subtitles of multiple items, e.g. the results of a
+    /// [`QueryResult`](crate::models::QueryResult).
+    ///
+    /// See [`SubtitleIndex::fetch`]. If the subtitles of an item can't be
+    /// downloaded, the remaining items are still indexed. The items that
+    /// failed are returned together with their errors.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mediathekviewweb::{subtitles::SubtitleIndex, Mediathek};
+    ///
+    /// let mediathek = Mediathek::new("my-user-agent".parse()?)?;
+    /// let result = mediathek
+    ///     .query_string("!ard #tagesschau", false)?
+    ///     .send()
+    ///     .await?;
+    ///
+    /// let mut index = SubtitleIndex::new();
+    /// let failed = index
+    ///     .fetch_all(&reqwest::Client::new(), result.results)
+    ///     .await;
+    /// for (item, error) in failed {
+    ///     eprintln!("no subtitles for {}: {error}", item.title);
+    /// }
+    /// for found in index.search("Bundesregierung") {
+    ///     println!("{} at {:?}", found.item.title, found.start());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "reqwest")]
+    pub async fn fetch_all(
+        &mut self,
+        http: &reqwest::Client,
+        items: impl IntoIterator<Item = Item>,
+    ) -> Vec<(Item, crate::Error)> {
+        let mut failed = Vec::new();
+        for item in items {
+            if let Err(e) = self.fetch_or_return(http, item).await {
+                failed.push(e);
+            }
+        }
+        failed
+    }
+
+    /// Download the subtitles of a stream of items, e.g. from
+    /// [`MediathekQueryBuilder::items`](crate::MediathekQueryBuilder::items).
+    ///
+    /// See [`SubtitleIndex::fetch_all`]. An error of the stream itself stops
+    /// the download and is returned.
+    #[cfg(feature = "reqwest")]
+    pub async fn fetch_stream(
+        &mut self,
+        http: &reqwest::Client,
+        items: impl futures_core::Stream<Item = crate::Result<Item>>,
+    ) -> crate::Result<Vec<(Item, crate::Error)>> {
+        use futures_util::{pin_mut, StreamExt};
+
+        pin_mut!(items);
+        let mut failed = Vec::new();
+        while let Some(item) = items.next().await {
+            if let Err(e) = self.fetch_or_return(http, item?).await {
+                failed.push(e);
+            }
+        }
+        Ok(failed)
+    }
+
+    /// Like [`fetch`](Self::fetch), but returns the item if it fails.
+    #[cfg(feature = "reqwest")]
+    async fn fetch_or_return(
+        &mut self,
+        http: &reqwest::Client,
+        item: Item,
+    ) -> Result<(), (Item, crate::Error)> {
+        match fetch_subtitles(http, &item).await {
+            Ok(Some(subtitles)) => self.insert(item, subtitles),
+            Ok(None) => {}
+            Err(e) => return Err((item, e)),
+        }
+        Ok(())
+    }
+
+    /// Search for a phrase in all indexed subtitles.
+    ///
+    /// The phrase must start and end at word boundaries. Matches are returned
+    /// in the order of the indexed items and their cues.
+    pub fn search(&self, phrase: &str) -> Vec<SubtitleMatch<'_>> {
+        let phrase = normalize(phrase);
+        if phrase.is_empty() {
+            return Vec::new();
+        }
+        let mut matches = Vec::new();
+        for document in &self.documents {
+            let text = &document.text;
+            let mut start = 0;
+            while let Some(found) = text[start..].find(&phrase) {
+                let offset = start + found;
+                start = offset + text[offset..].chars().next().map_or(1, char::len_utf8);
+                // only match whole words, so `rat` doesn't match `Bundesrat`
+                if !is_word_boundary(text, offset) || !is_word_boundary(text, offset + phrase.len())
+                {
+                    continue;
+                }
+                let first = document.offsets.partition_point(|&o| o <= offset) - 1;
+                let last = document
+                    .offsets
+                    .partition_point(|&o| o < offset + phrase.len())
+                    - 1;
+                matches.push(SubtitleMatch {
+                    item: &document.item,
+                    cues: &document.cues[first..=last],
+                });
+            }
+        }
+        matches
+    }
+}
+
+/// A phrase found by [`SubtitleIndex::search`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubtitleMatch<'a> {
+    pub item: &'a Item,
+    /// The cues that contain the phrase. Usually this is a single cue, but
+    /// a phrase can span multiple cues.
+    pub cues: &'a [Cue],
+}
+impl SubtitleMatch<'_> {
+    /// The time at which the phrase is shown.
+    pub fn start(&self) -> Duration {
+        self.cues[0].start
+    }
+
+    /// The time at which the phrase disappears.
+    pub fn end(&self) -> Duration {
+        self.cues[self.cues.len() - 1].end
+    }
+}
+
+/// Download the subtitles of an item, moving subtitles that start at the
+/// broadcast timecode to the start of the video.
+#[cfg(feature = "reqwest")]
+async fn fetch_subtitles(http: &reqwest::Client, item: &Item) -> crate::Result<Option<Subtitles>> {
+    let Some(url) = &item.url_subtitle else {
+        return Ok(None);
+    };
+    let mut subtitles = Subtitles::fetch(http, url).await?;
+    if subtitles
+        .cues
+        .first()
+        .is_some_and(|cue| cue.start >= BROADCAST_OFFSET)
+        && item
+            .duration
+            .is_none_or(|duration| duration < BROADCAST_OFFSET)
+    {
+        subtitles.advance(BROADCAST_OFFSET);
+    }
+    Ok(Some(subtitles))
+}
+
+/// Whether a byte offset of a text doesn't split a word.
+fn is_word_boundary(text: &str, offset: usize) -> bool {
+    let before = text[..offset].chars().next_back();
+    let after = text[offset..].chars().next();
+    !(before.is_some_and(char::is_alphanumeric) && after.is_some_and(char::is_alphanumeric))
+}
+
+/// Convert text to lowercase and replace line breaks and consecutive spaces
+/// by a single space.
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_time, Color, Cue, Span, SubtitleIndex, Subtitles};
+
+    const EBU_TT: &str = include_str!("subtitles/ebu-tt.xml");
+    const TTML: &str = include_str!("subtitles/ttml.xml");
+
+    fn span(text: &str, color: Option<Color>) -> Span {
+        Span {
+            text: text.to_owned(),
+            color,
+        }
+    }
+
+    #[test]
+    fn test_ebu_tt() {
+        let mut subtitles: Subtitles = EBU_TT.parse().unwrap();
+        subtitles.advance(Duration::from_secs(10 * 3600));
+
+        assert_eq!(
+            subtitles.cues,
+            [
+                Cue {
+                    start: Duration::from_millis(1200),
+                    end: Duration::from_millis(4000),
+                    spans: vec![span(
+                        "Guten Abend, meine Damen\nund Herren.",
+                        Some(Color::WHITE)
+                    )],
+                },
+                Cue {
+                    start: Duration::from_millis(4080),
+                    end: Duration::from_millis(7520),
+                    spans: vec![span(
+                        "Die Gewerkschaft & der Verband\nhaben sich geeinigt.",
+                        Some(Color::YELLOW)
+                    )],
+                },
+                Cue {
+                    start: Duration::from_millis(7600),
+                    end: Duration::from_millis(10000),
+                    spans: vec![
+                        span("Wir sind „zufrieden“", Some(Color::CYAN)),
+                        span(" - sagt der Sprecher.", Some(Color::WHITE)),
+                    ],
+                },
+            ]
+        );
+        assert_eq!(
+            subtitles.cues[0].text(),
+            "Guten Abend, meine Damen\nund Herren."
+        );
+
+        assert_eq!(
+            subtitles.to_srt(),
+            "1
+00:00:01,200 --> 00:00:04,000
+Guten Abend, meine Damen
+und Herren.
+
+2
+00:00:04,080 --> 00:00:07,520
+<font color=\"#ffff00\">Die Gewerkschaft &amp; der Verband
+haben sich geeinigt.</font>
+
+3
+00:00:07,600 --> 00:00:10,000
+<font color=\"#00ffff\">Wir sind „zufrieden“</font> - sagt der Sprecher.
+
+"
+        );
+        assert_eq!(
+            subtitles.to_webvtt(),
+            "WEBVTT
+
+1
+00:00:01.200 --> 00:00:04.000
+Guten Abend, meine Damen
+und Herren.
+
+2
+00:00:04.080 --> 00:00:07.520
+<c.yellow>Die Gewerkschaft &amp; der Verband
+haben sich geeinigt.</c>
+
+3
+00:00:07.600 --> 00:00:10.000
+<c.cyan>Wir sind „zufrieden“</c> - sagt der Sprecher.
+
+"
+        );
+    }
+
+    #[test]
+    fn test_ttml() {
+        let subtitles: Subtitles = TTML.parse().unwrap();
+
+        assert_eq!(
+            subtitles.cues,
+            [
+                Cue {
+                    start: Duration::from_millis(1200),
+                    end: Duration::from_millis(3400),
+                    spans: vec![span("Das Wetter für morgen:", Some(Color::WHITE))],
+                },
+                Cue {
+                    start: Duration::from_millis(3480),
+                    end: Duration::from_millis(5980),
+                    spans: vec![span("Im Norden regnet es.", Some(Color::LIME))],
+                },
+                Cue {
+                    start: Duration::from_millis(6000),
+                    end: Duration::from_millis(7500),
+                    spans: vec![span("Im Süden <scheint> die Sonne.", Some(Color::MAGENTA))],
+                },
+            ]
+        );
+        assert!(subtitles
+            .to_webvtt()
+            .ends_with("<c.magenta>Im Süden &lt;scheint&gt; die Sonne.</c>\n\n"));
+        assert!(subtitles
+            .to_srt()
+            .ends_with("<font color=\"#ff00ff\">Im Süden &lt;scheint&gt; die Sonne.</font>\n\n"));
+    }
+
+    #[test]
+    fn test_time_base() {
+        let begin = |tt: &str| tt.parse::<Subtitles>().unwrap().cues[0].start;
+        let body = r#"<body><p begin="50t" end="100t">Text</p></body></tt>"#;
+
+        // one tick per second by default
+        assert_eq!(begin(&format!("<tt>{body}")), Duration::from_secs(50));
+        // the effective frame rate if a frame rate is set
+        assert_eq!(
+            begin(&format!(
+                r#"<tt xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:frameRateMultiplier="1000 1001" ttp:frameRate="25">{body}"#
+            )),
+            Duration::from_secs_f64(50.0 / (25.0 * 1000.0 / 1001.0))
+        );
+        assert_eq!(
+            begin(&format!(
+                r#"<tt xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:tickRate="10" ttp:frameRate="25">{body}"#
+            )),
+            Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(
+            parse_time("10:00:01.200", 25.0, 25.0),
+            Some(Duration::from_millis(36_001_200))
+        );
+        assert_eq!(
+            parse_time("00:00:01:05", 25.0, 25.0),
+            Some(Duration::from_millis(1200))
+        );
+        assert_eq!(
+            parse_time("1.5s", 25.0, 25.0),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            parse_time("250ms", 25.0, 25.0),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(parse_time("2m", 25.0, 25.0), Some(Duration::from_secs(120)));
+        assert_eq!(parse_time("50f", 25.0, 25.0), Some(Duration::from_secs(2)));
+        assert_eq!(
+            parse_time("15000000t", 25.0, 10_000_000.0),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(parse_time("1.5", 25.0, 25.0), None);
+        assert_eq!(parse_time("1:2", 25.0, 25.0), None);
+        assert_eq!(parse_time("-1s", 25.0, 25.0), None);
+        assert_eq!(parse_time("99999999999999999:00:00", 25.0, 25.0), None);
+        assert_eq!(parse_time("00:999999999999999999:00", 25.0, 25.0), None);
+
+        let xml = "<tt><body><div>\
+                   <p begin=\"99999999999999999:00:00\" end=\"1s\">Text</p>\
+                   </div></body></tt>";
+        let error = xml.parse::<Subtitles>().unwrap_err();
+        assert_eq!(
+            error.message,
+            "invalid time expression `99999999999999999:00:00`"
+        );
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(Color::parse("#FFFF00"), Some(Color::YELLOW));
+        assert_eq!(
+            Color::parse("#000000c2"),
+            Some(Color {
+                alpha: 0xc2,
+                ..Color::BLACK
+            })
+        );
+        assert_eq!(Color::parse("rgb(0, 255, 255)"), Some(Color::CYAN));
+        assert_eq!(Color::parse("Fuchsia"), Some(Color::MAGENTA));
+        assert_eq!(Color::parse("#fff"), None);
+        assert_eq!(Color::parse("orange"), None);
+        assert_eq!(Color::YELLOW.to_string(), "#ffff00");
+    }
+
+    #[test]
+    fn test_invalid() {
+        let error = "<tt><body><p end=\"1s\">Text</p></body></tt>"
+            .parse::<Subtitles>()
+            .unwrap_err();
+        assert_eq!(error.message, "missing `begin` attribute");
+
+        assert!("<tt><body><p begin=\"1s\" end=\"2s\">Text</body></tt>"
+            .parse::<Subtitles>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_subtitle_index() {
+        let mut index = SubtitleIndex::new();
+        let items = crate::mock::fixtures();
+        index.insert(items[0].clone(), EBU_TT.parse().unwrap());
+        index.insert(items[1].clone(), TTML.parse().unwrap());
+        assert_eq!(index.len(), 2);
+
+        let matches = index.search("MEINE  damen");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].item, &items[0]);
+        assert_eq!(matches[0].start(), Duration::from_millis(36_001_200));
+
+        // phrases are found across line breaks and cues
+        let matches = index.search("herren. die gewerkschaft");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].cues.len(), 2);
+        assert_eq!(matches[0].start(), Duration::from_millis(36_001_200));
+        assert_eq!(matches[0].end(), Duration::from_millis(36_007_520));
+
+        let matches = index.search("im");
+        assert_eq!(
+            matches
+                .iter()
+                .map(|m| (&m.item.id, m.start()))
+                .collect::<Vec<_>>(),
+            [
+                (&items[1].id, Duration::from_millis(3480)),
+                (&items[1].id, Duration::from_millis(6000)),
+            ]
+        );
+
+        // only whole words are matched
+        assert!(index.search("ewerkschaft").is_empty());
+        assert!(index.search("gewerk").is_empty());
+        assert_eq!(index.search("verband haben").len(), 1);
+        assert!(index.search("schnee").is_empty());
+        assert!(index.search(" ").is_empty());
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_fetch_subtitle_index() -> Result<(), Box<dyn std::error::Error>> {
+        let server = crate::mock::MockServer::start()?;
+        server.serve_file("/subtitles.xml", EBU_TT);
+        let mut items = crate::mock::fixtures();
+        items[0].url_subtitle = Some(format!("{}/subtitles.xml", server.url()));
+        items[1].url_subtitle = None;
+        items[2].url_subtitle = Some(format!("{}/missing.xml", server.url()));
+        items[3].url_subtitle = Some(format!("{}/subtitles.xml", server.url()));
+        let missing = items[2].id.clone();
+
+        // the missing subtitles don't prevent indexing the remaining items
+        let mut index = SubtitleIndex::new();
+        let failed = index
+            .fetch_all(&reqwest::Client::new(), items.drain(..4))
+            .await;
+        assert_eq!(index.len(), 2);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0.id, missing);
+        assert!(matches!(failed[0].1, crate::Error::Status(404)));
+
+        // the broadcast timecode is removed
+        let matches = index.search("geeinigt");
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].start(), Duration::from_millis(4080));
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+//! Abstraction over the HTTP client that is used to communicate with the
+//! server.
+//!
+//! By default, [`Mediathek`](crate::Mediathek) uses [`ReqwestTransport`]
+//! which is available if one of the TLS features is enabled. Other HTTP
+//! clients (or an in-process fake for tests) can be used by implementing
+//! [`Transport`] and passing it to
+//! [`Mediathek::with_transport`](crate::Mediathek::with_transport).
+//!
+//! # Example
+//! ```rust
+//! use mediathekviewweb::transport::{BoxFuture, Request, Response, Transport, TransportError};
+//!
+//! #[derive(Debug)]
+//! struct EmptyResults;
+//!
+//! impl Transport for EmptyResults {
+//!     fn send(&self, _request: Request) -> BoxFuture<'_, Result<Response, TransportError>> {
+//!         Box::pin(async {
+//!             Ok(Response {
+//!                 status: 200,
+//!                 headers: Vec::new(),
+//!                 body: br#"{"err":null,"result":{"queryInfo":{"filmlisteTimestamp":0,"resultCount":0,"searchEngineTime":"0.00","totalResults":0},"results":[]}}"#.to_vec(),
+//!             })
+//!         })
+//!     }
+//! }
+//!
+//! let mediathek = mediathekviewweb::Mediathek::with_transport(EmptyResults);
+//! ```
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+/// An owned dynamically typed [`Future`] as returned by [`Transport::send`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An error returned by a [`Transport`], e.g. if the connection failed.
+pub type TransportError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Sends HTTP requests to a MediathekViewWeb server.
+pub trait Transport: std::fmt::Debug + Send + Sync {
+    /// Send a request to the server and return its response.
+    ///
+    /// Responses with an unsuccessful status code must be returned as a
+    /// [`Response`], not as an error.
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, TransportError>>;
+
+    /// Wait for the specified duration, e.g. before retrying a request.
+    ///
+    /// The default implementation starts a timer thread and works with every
+    /// async runtime. Implementations should override it with the timer of
+    /// their runtime.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(ThreadSleep {
+            duration,
+            state: None,
+        })
+    }
+}
+
+/// A future that completes after a timer thread has slept for a duration.
+struct ThreadSleep {
+    duration: Duration,
+    /// Whether the timer has elapsed and the waker of the last poll.
+    state: Option<Arc<Mutex<(bool, Waker)>>>,
+}
+impl Future for ThreadSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.duration.is_zero() {
+            return Poll::Ready(());
+        }
+        match &self.state {
+            Some(state) => {
+                let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                if state.0 {
+                    return Poll::Ready(());
+                }
+                state.1.clone_from(cx.waker());
+            }
+            None => {
+                let state = Arc::new(Mutex::new((false, cx.waker().clone())));
+                std::thread::spawn({
+                    let state = state.clone();
+                    let duration = self.duration;
+                    move || {
+                        std::thread::sleep(duration);
+                        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                        state.0 = true;
+                        state.1.wake_by_ref();
+                    }
+                });
+                self.state = Some(state);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+/// The HTTP method of a [`Request`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Method {
+    Get,
+    Post,
+}
+
+/// A request to a MediathekViewWeb server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Request {
+    pub method: Method,
+    /// The path relative to the server's base URL, e.g. `/api/query`.
+    pub path: String,
+    /// Additional headers as `(name, value)` pairs.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// The response of a MediathekViewWeb server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The response headers as `(name, value)` pairs.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+impl Response {
+    /// Whether the status code indicates success (`2xx`).
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Return the value of the first header with the specified name, ignoring
+    /// case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A [`Transport`] based on [`reqwest`].
+#[cfg(feature = "reqwest")]
+#[derive(Clone, Debug)]
+pub struct ReqwestTransport {
+    base_url: String,
+    http: reqwest::Client,
+}
+#[cfg(feature = "reqwest")]
+impl ReqwestTransport {
+    /// Create a new transport that sends requests to the server hosted at
+    /// `base_url` using an existing [`reqwest::Client`].
+    pub fn new(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        let mut base_url: String = base_url.into();
+        if base_url.ends_with('/') {
+            base_url.truncate(base_url.len() - 1)
+        }
+        Self { base_url, http }
+    }
+}
+#[cfg(feature = "reqwest")]
+impl Transport for ReqwestTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, TransportError>> {
+        Box::pin(async move {
+            let method = match request.method {
+                Method::Get => reqwest::Method::GET,
+                Method::Post => reqwest::Method::POST,
+            };
+            let mut builder = self
+                .http
+                .request(method, format!("{}{}", self.base_url, request.path))
+                .body(request.body);
+            for (name, value) in request.headers {
+                builder = builder.header(name, value);
+            }
+
+            let response = builder.send().await?;
+            let status = response.status().as_u16();
+            let headers = response
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.as_str().to_owned(), value.to_str().ok()?.to_owned()))
+                })
+                .collect();
+            let body = response.bytes().await?.to_vec();
+
+            Ok(Response {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}